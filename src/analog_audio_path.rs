@@ -1,6 +1,10 @@
 //! Configuration for analog audio path
 
+use core::fmt;
+
 use crate::bitmask::BitMask;
+use crate::decode;
+use crate::display::{dac_select_str, Enabled, Plain};
 use crate::EnableDisable;
 
 pub struct InputSelect<'a> {
@@ -45,7 +49,51 @@ impl<'a> DacSelect<'a> {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+pub struct SidetoneAttenuation<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> SidetoneAttenuation<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+
+        SidetoneAttenuation { index, bitmask }
+    }
+
+    /// -6dB
+    pub fn minus_6(&mut self) {
+        self.bitmask.apply(self.index, 2, 0b00)
+    }
+
+    /// -9dB
+    pub fn minus_9(&mut self) {
+        self.bitmask.apply(self.index, 2, 0b01)
+    }
+
+    /// -12dB
+    pub fn minus_12(&mut self) {
+        self.bitmask.apply(self.index, 2, 0b10)
+    }
+
+    /// -15dB
+    pub fn minus_15(&mut self) {
+        self.bitmask.apply(self.index, 2, 0b11)
+    }
+
+    /// Set side tone attenuation to nearest representable value
+    ///
+    /// The WM8731 only supports -6dB, -9dB, -12dB and -15dB, so `dB_gain` is rounded to the
+    /// nearest of those; for example -10dB becomes -9dB, and anything above -6dB or below -15dB
+    /// becomes -6dB or -15dB respectively.
+    #[allow(non_snake_case)]
+    pub fn nearest_dB(&mut self, dB_gain: i16) {
+        let steps = (-6 - dB_gain.clamp(-15, -6) + 1) / 3;
+        self.bitmask.apply(self.index, 2, steps as u16)
+    }
+}
+
+#[derive(Copy, Clone)]
 pub struct AnalogAudioPath {
     pub(crate) data: u16,
 }
@@ -58,38 +106,79 @@ impl AnalogAudioPath {
     }
 
     /// Microphone input level boost
-    pub fn mic_boost(&mut self) -> EnableDisable {
+    pub fn mic_boost(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(0, &mut self.data)
     }
 
     /// Mic input mute to ADC
-    pub fn mute_mic(&mut self) -> EnableDisable {
+    pub fn mute_mic(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(1, &mut self.data)
     }
 
     /// Microphone/line input select to ADC
-    pub fn input_select(&mut self) -> InputSelect {
+    pub fn input_select(&mut self) -> InputSelect<'_> {
         InputSelect::new(2, &mut self.data)
     }
 
     /// Bypass switch
-    pub fn bypass(&mut self) -> EnableDisable {
+    pub fn bypass(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(3, &mut self.data)
     }
 
     /// DAC select
-    pub fn dac_select(&mut self) -> DacSelect {
+    pub fn dac_select(&mut self) -> DacSelect<'_> {
         DacSelect::new(4, &mut self.data)
     }
 
     /// Side tone switch
-    pub fn sidetone(&mut self) -> EnableDisable {
+    pub fn sidetone(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(5, &mut self.data)
     }
 
     /// Side tone attenuation
-    pub fn sidetone_attenuation(&mut self) {
-        // TODO: figure this out
-        self.data |= 0b0_0000_0000
+    pub fn sidetone_attenuation(&mut self) -> SidetoneAttenuation<'_> {
+        SidetoneAttenuation::new(6, &mut self.data)
+    }
+}
+
+impl fmt::Debug for AnalogAudioPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let aap = decode::analog_audio_path(self.data);
+
+        f.debug_struct("AnalogAudioPath")
+            .field("mic_boost", &Enabled(aap.mic_boost))
+            .field("mute_mic", &Enabled(aap.mute_mic))
+            .field("input_select", &Plain(aap.input))
+            .field("bypass", &Enabled(aap.bypass))
+            .field("dac_select", &Plain(dac_select_str(aap.dac_select)))
+            .field("sidetone", &Enabled(aap.sidetone))
+            .field("sidetone_attenuation", &Plain(aap.sidetone_attenuation))
+            .finish()
+    }
+}
+
+impl fmt::Display for AnalogAudioPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AnalogAudioPath {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let aap = decode::analog_audio_path(self.data);
+
+        defmt::write!(
+            f,
+            "AnalogAudioPath {{ mic_boost: {}, mute_mic: {}, input_select: {=str}, bypass: {}, \
+             dac_select: {=str}, sidetone: {}, sidetone_attenuation: {=str} }}",
+            Enabled(aap.mic_boost),
+            Enabled(aap.mute_mic),
+            aap.input.as_str(),
+            Enabled(aap.bypass),
+            dac_select_str(aap.dac_select),
+            Enabled(aap.sidetone),
+            aap.sidetone_attenuation.as_str()
+        )
     }
 }
@@ -0,0 +1,319 @@
+//! Async I²C driver for the control interface
+//!
+//! The async counterpart of [`driver::Wm8731`](crate::driver::Wm8731), for use with executors
+//! such as Embassy. Registers are encoded the same way and errors are reported with the same
+//! [`Error`] type, and the interface state is tracked in the type in the same way. Requires the
+//! `async` feature.
+
+use core::marker::PhantomData;
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::i2c::I2c;
+
+use crate::analog_audio_path::AnalogAudioPath;
+use crate::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+use crate::digital_audio_path::DigitalAudioPath;
+use crate::driver::{
+    check_state, Active, Error, Inactive, State, ACTIVE, ADDRESS_CSB_LOW, POWER_DOWN, RESET,
+};
+use crate::headphone_out::HeadphoneOut;
+use crate::line_in::LineIn;
+use crate::power_down::PowerDown;
+use crate::sampling::Sampling;
+use crate::sequence::{self, Step};
+use crate::{Register, WM8731};
+
+/// WM8731 driver over an async I²C bus
+pub struct AsyncWm8731<I2C, S = Inactive> {
+    i2c: I2C,
+    address: u8,
+    state: PhantomData<S>,
+}
+
+impl<I2C: I2c> AsyncWm8731<I2C> {
+    /// Create a driver for a codec with the CSB pin tied low
+    pub fn new(i2c: I2C) -> Self {
+        Self::new_with_address(i2c, ADDRESS_CSB_LOW)
+    }
+
+    /// Create a driver for a codec at the given 7-bit I²C address
+    pub fn new_with_address(i2c: I2C, address: u8) -> Self {
+        AsyncWm8731 {
+            i2c,
+            address,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<I2C: I2c, S> AsyncWm8731<I2C, S> {
+    /// The same driver in another state
+    fn into_state<T>(self) -> AsyncWm8731<I2C, T> {
+        AsyncWm8731 {
+            i2c: self.i2c,
+            address: self.address,
+            state: PhantomData,
+        }
+    }
+
+    /// Give back the I²C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Write a register to the codec, without checking the state
+    async fn write_unchecked(&mut self, register: Register) -> Result<(), Error<I2C::Error>> {
+        self.i2c
+            .write(self.address, &register.to_bytes()?)
+            .await
+            .map_err(Error::from_i2c)
+    }
+
+    /// Perform each step in order, without checking the state
+    async fn run_steps<T: IntoIterator<Item = Step>, D: DelayNs>(
+        &mut self,
+        steps: T,
+        delay: &mut D,
+    ) -> Result<(), Error<I2C::Error>> {
+        for step in steps {
+            match step {
+                Step::Write(register) => self.write_unchecked(register).await?,
+                Step::Delay { ms } => delay.delay_ms(ms).await,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Perform the steps, then move to state `T`; on failure, give back the driver unchanged
+    async fn transition<T, U: IntoIterator<Item = Step>, D: DelayNs>(
+        mut self,
+        steps: U,
+        delay: &mut D,
+    ) -> Result<AsyncWm8731<I2C, T>, (Self, Error<I2C::Error>)> {
+        match self.run_steps(steps, delay).await {
+            Ok(()) => Ok(self.into_state()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I2C: I2c, S: State> AsyncWm8731<I2C, S> {
+    /// Write a register to the codec
+    ///
+    /// Registers are checked against the state as by
+    /// [`Wm8731::write`](crate::driver::Wm8731::write).
+    pub async fn write(&mut self, register: Register) -> Result<(), Error<I2C::Error>> {
+        check_state::<S, I2C::Error>(register)?;
+        self.write_unchecked(register).await
+    }
+
+    /// Perform each step of a [`sequence`] in order
+    ///
+    /// Each write is checked as by [`write`](Self::write), and the steps stop at the first one
+    /// that fails.
+    pub async fn run<T: IntoIterator<Item = Step>, D: DelayNs>(
+        &mut self,
+        steps: T,
+        delay: &mut D,
+    ) -> Result<(), Error<I2C::Error>> {
+        for step in steps {
+            match step {
+                Step::Write(register) => self.write(register).await?,
+                Step::Delay { ms } => delay.delay_ms(ms).await,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the left line input control register
+    pub async fn left_line_in<F: FnOnce(&mut LineIn)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::left_line_in(c)).await
+    }
+
+    /// Write the right line input control register
+    pub async fn right_line_in<F: FnOnce(&mut LineIn)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::right_line_in(c)).await
+    }
+
+    /// Write the left headphone out control register
+    pub async fn left_headphone_out<F: FnOnce(&mut HeadphoneOut)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::left_headphone_out(c)).await
+    }
+
+    /// Write the right headphone out control register
+    pub async fn right_headphone_out<F: FnOnce(&mut HeadphoneOut)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::right_headphone_out(c)).await
+    }
+
+    /// Write the left and right line input control registers in as few writes as possible
+    pub async fn stereo_line_in<F: FnOnce(&mut LineIn), G: FnOnce(&mut LineIn)>(
+        &mut self,
+        left: F,
+        right: G,
+    ) -> Result<(), Error<I2C::Error>> {
+        for register in WM8731::stereo_line_in(left, right).iter() {
+            self.write(*register).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the left and right headphone out control registers in as few writes as possible
+    pub async fn stereo_headphone_out<
+        F: FnOnce(&mut HeadphoneOut),
+        G: FnOnce(&mut HeadphoneOut),
+    >(
+        &mut self,
+        left: F,
+        right: G,
+    ) -> Result<(), Error<I2C::Error>> {
+        for register in WM8731::stereo_headphone_out(left, right).iter() {
+            self.write(*register).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the analog audio path control register
+    pub async fn analog_audio_path<F: FnOnce(&mut AnalogAudioPath)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::analog_audio_path(c)).await
+    }
+
+    /// Write the digital audio path control register
+    pub async fn digital_audio_path<F: FnOnce(&mut DigitalAudioPath)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::digital_audio_path(c)).await
+    }
+
+    /// Write the power down control register
+    pub async fn power_down<F: FnOnce(&mut PowerDown)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::power_down(c)).await
+    }
+}
+
+impl<I2C: I2c> AsyncWm8731<I2C, Inactive> {
+    /// Write the digital audio interface format control register
+    pub async fn digital_audio_interface_format<F: FnOnce(&mut DigitalAudioInterfaceFormat)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::digital_audio_interface_format(c)).await
+    }
+
+    /// Write the sampling control register
+    pub async fn sampling<F: FnOnce(&mut Sampling)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::sampling(c)).await
+    }
+
+    /// Activate the digital audio interface
+    ///
+    /// If the write fails, the error is returned along with the still inactive driver.
+    pub async fn activate(self) -> Result<AsyncWm8731<I2C, Active>, (Self, Error<I2C::Error>)> {
+        let steps = [Step::Write(WM8731::active().active())];
+        self.transition(steps, &mut NoDelay).await
+    }
+
+    /// Reset all registers to their defaults
+    pub async fn reset(&mut self) -> Result<(), Error<I2C::Error>> {
+        self.write(WM8731::reset()).await
+    }
+
+    /// Run [`sequence::power_up`], which activates the interface
+    ///
+    /// `configuration` must not include the power down, active or reset registers; if it does,
+    /// nothing is written and [`Error::State`] is returned. If a step fails, the error is returned
+    /// along with the driver, still typed as inactive, although the interface may have been
+    /// activated.
+    pub async fn run_power_up<D: DelayNs>(
+        self,
+        power: PowerDown,
+        configuration: &[Register],
+        settle_ms: u32,
+        delay: &mut D,
+    ) -> Result<AsyncWm8731<I2C, Active>, (Self, Error<I2C::Error>)> {
+        if let Some(register) = configuration
+            .iter()
+            .find(|register| matches!(register.address, POWER_DOWN | ACTIVE | RESET))
+        {
+            return Err((self, Error::State(register.address)));
+        }
+
+        let steps = sequence::power_up(power, configuration, settle_ms);
+        self.transition(steps, delay).await
+    }
+}
+
+impl<I2C: I2c> AsyncWm8731<I2C, Active> {
+    /// Deactivate the digital audio interface
+    ///
+    /// If the write fails, the error is returned along with the still active driver.
+    pub async fn deactivate(self) -> Result<AsyncWm8731<I2C, Inactive>, (Self, Error<I2C::Error>)> {
+        let steps = [Step::Write(WM8731::active().inactive())];
+        self.transition(steps, &mut NoDelay).await
+    }
+
+    /// Reset all registers to their defaults, which leaves the interface inactive
+    ///
+    /// If the write fails, the error is returned along with the still active driver.
+    pub async fn reset(self) -> Result<AsyncWm8731<I2C, Inactive>, (Self, Error<I2C::Error>)> {
+        let steps = [Step::Write(WM8731::reset())];
+        self.transition(steps, &mut NoDelay).await
+    }
+
+    /// Run [`sequence::power_down`], which deactivates the interface
+    ///
+    /// If a step fails, the error is returned along with the driver, still typed as active.
+    pub async fn run_power_down<D: DelayNs>(
+        self,
+        power: PowerDown,
+        path: DigitalAudioPath,
+        delay: &mut D,
+    ) -> Result<AsyncWm8731<I2C, Inactive>, (Self, Error<I2C::Error>)> {
+        self.transition(sequence::power_down(power, path), delay)
+            .await
+    }
+
+    /// Run [`sequence::standby`], which deactivates the interface
+    ///
+    /// If a step fails, the error is returned along with the driver, still typed as active.
+    pub async fn run_standby<D: DelayNs>(
+        self,
+        power: PowerDown,
+        path: DigitalAudioPath,
+        delay: &mut D,
+    ) -> Result<AsyncWm8731<I2C, Inactive>, (Self, Error<I2C::Error>)> {
+        self.transition(sequence::standby(power, path), delay).await
+    }
+}
+
+/// Delay for transitions made of writes only
+struct NoDelay;
+
+impl DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
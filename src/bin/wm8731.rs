@@ -0,0 +1,13 @@
+//! Build, decode and export WM8731 register writes from the command line
+//!
+//! ```text
+//! wm8731 build --line-in-gain 3dB --format i2s --bits 24 --rate 48k
+//! wm8731 init --config board.toml --output i2cset --bus 1
+//! wm8731 decode 0x0e4a 0x1001
+//! ```
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    wm8731::cli::main()
+}
@@ -0,0 +1,570 @@
+//! The `wm8731` command line tool
+//!
+//! Turns flags and TOML configurations into register writes, and prints them as hex words, C or
+//! Rust arrays or `i2cset` commands. The `wm8731` binary just calls [`main`]. Requires the `cli`
+//! feature.
+
+// The clap derives expect the std prelude
+use std::borrow::ToOwned;
+use std::boxed::Box;
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::string::String;
+use std::vec::Vec;
+use std::{eprintln, format, fs};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::config::{AudioFormat, CodecConfig, Deemphasis, Input, WordLength};
+use crate::decode::decode;
+use crate::driver::ADDRESS_CSB_LOW;
+use crate::rate_solver::SampleRate;
+use crate::sequence::Step;
+use crate::validate::{validate, Warning};
+use crate::{ConfigError, Register};
+
+#[derive(Parser)]
+#[command(
+    name = "wm8731",
+    version,
+    about = "Generate and decode WM8731 register writes"
+)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Command,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Print the registers that differ from the reset defaults
+    Build(Build),
+    /// Print the full power-up sequence: reset, outputs off, every register, activate, outputs on
+    Init(Build),
+    /// Print the fields of 16-bit control words, given as hex arguments or on stdin
+    Decode {
+        /// Control words such as 0x0e4a, or address=value pairs such as 7=0x4a
+        words: Vec<String>,
+    },
+}
+
+#[derive(Args)]
+pub(crate) struct Build {
+    /// TOML file holding a whole codec configuration to start from
+    #[arg(long)]
+    config: Option<String>,
+
+    /// Line input gain for both channels, from -34.5dB to 12dB in 1.5dB steps
+    #[arg(long, value_parser = parse_db, allow_hyphen_values = true)]
+    line_in_gain: Option<f32>,
+    /// Mute both line inputs
+    #[arg(long)]
+    line_in_mute: Option<bool>,
+    /// Headphone volume for both channels, from -73dB to 6dB, or "mute"
+    #[arg(long, allow_hyphen_values = true)]
+    headphone_volume: Option<String>,
+    /// Input to the ADC
+    #[arg(long)]
+    input: Option<InputArg>,
+    /// Boost the microphone input by 20dB
+    #[arg(long)]
+    mic_boost: Option<bool>,
+    /// Route the line input straight to the outputs
+    #[arg(long)]
+    bypass: Option<bool>,
+    /// Route the DAC to the outputs
+    #[arg(long)]
+    dac_select: Option<bool>,
+    /// De-emphasis filter
+    #[arg(long)]
+    deemphasis: Option<DeemphasisArg>,
+    /// ADC high pass filter
+    #[arg(long)]
+    adc_hpf: Option<bool>,
+    /// DAC soft mute
+    #[arg(long)]
+    dac_mute: Option<bool>,
+    /// Comma separated parts to power up, powering down the rest: line, mic, adc, dac,
+    /// output, oscillator, clock-output
+    #[arg(long, value_delimiter = ',')]
+    power: Option<Vec<PowerArg>>,
+    /// Audio data format
+    #[arg(long)]
+    format: Option<FormatArg>,
+    /// Bits per sample
+    #[arg(long, value_parser = ["16", "20", "24", "32"])]
+    bits: Option<String>,
+    /// Generate BCLK and LRCLK
+    #[arg(long)]
+    master: Option<bool>,
+    /// MCLK frequency, such as 12.288M or 12288000
+    #[arg(long, value_parser = parse_hz)]
+    mclk: Option<u32>,
+    /// ADC and DAC sample rate, such as 48k or 44.1k
+    #[arg(long, value_parser = parse_rate)]
+    rate: Option<SampleRate>,
+    /// ADC sample rate, overriding --rate
+    #[arg(long, value_parser = parse_rate)]
+    adc_rate: Option<SampleRate>,
+    /// DAC sample rate, overriding --rate
+    #[arg(long, value_parser = parse_rate)]
+    dac_rate: Option<SampleRate>,
+    /// Activate the digital audio interface
+    #[arg(long)]
+    active: Option<bool>,
+    /// Milliseconds to wait for VMID to charge before powering up the outputs (init only); this
+    /// depends on the VMID decoupling capacitor
+    #[arg(long, default_value_t = 250)]
+    settle_ms: u32,
+
+    /// How to print the registers
+    #[arg(long, value_enum, default_value_t = Output::Hex)]
+    output: Output,
+    /// I²C bus number for i2cset output
+    #[arg(long, default_value_t = 1)]
+    bus: u32,
+    /// I²C address for i2cset output
+    #[arg(long, value_parser = parse_u8, default_value_t = ADDRESS_CSB_LOW)]
+    address: u8,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum InputArg {
+    Line,
+    Mic,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum DeemphasisArg {
+    Off,
+    #[value(name = "32k")]
+    Khz32,
+    #[value(name = "44.1k")]
+    Khz441,
+    #[value(name = "48k")]
+    Khz48,
+}
+
+#[derive(Copy, Clone, PartialEq, ValueEnum)]
+enum PowerArg {
+    Line,
+    Mic,
+    Adc,
+    Dac,
+    Output,
+    Oscillator,
+    ClockOutput,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum FormatArg {
+    I2s,
+    Left,
+    Right,
+    Dsp,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+pub(crate) enum Output {
+    /// One 16-bit control word per line with its decoded fields
+    Hex,
+    /// A C array of 16-bit control words
+    C,
+    /// A Rust array of `Register`s
+    Rust,
+    /// i2c-tools command lines
+    I2cset,
+}
+
+/// A gain such as "3dB", "-34.5dB" or "1.5"
+pub(crate) fn parse_db(s: &str) -> Result<f32, String> {
+    let number = s
+        .strip_suffix("dB")
+        .or_else(|| s.strip_suffix("db"))
+        .unwrap_or(s);
+    number
+        .parse()
+        .map_err(|_| format!("`{}` is not a gain in dB", s))
+}
+
+/// A frequency such as "12.288M", "48k" or "12000000"
+pub(crate) fn parse_hz(s: &str) -> Result<u32, String> {
+    let (number, scale) = match s.char_indices().last() {
+        Some((i, 'M')) | Some((i, 'm')) => (&s[..i], 1_000_000.0),
+        Some((i, 'k')) | Some((i, 'K')) => (&s[..i], 1_000.0),
+        _ => (s, 1.0),
+    };
+    let hz: f64 = number
+        .parse()
+        .map_err(|_| format!("`{}` is not a frequency", s))?;
+
+    Ok((hz * scale).round() as u32)
+}
+
+/// A sample rate the codec supports, such as "48k" or "44.1k"
+pub(crate) fn parse_rate(s: &str) -> Result<SampleRate, String> {
+    Ok(match parse_hz(s)? {
+        8_000 => SampleRate::Hz8000,
+        8_018 => SampleRate::Hz8018,
+        8_021 => SampleRate::Hz8021,
+        32_000 => SampleRate::Hz32000,
+        44_100 => SampleRate::Hz44100,
+        48_000 => SampleRate::Hz48000,
+        88_200 => SampleRate::Hz88200,
+        96_000 => SampleRate::Hz96000,
+        _ => return Err(format!("`{}` is not a supported sample rate", s)),
+    })
+}
+
+/// A number in decimal or, with a 0x prefix, hex
+fn parse_u8(s: &str) -> Result<u8, String> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("`{}` is not a byte", s))
+}
+
+/// A control word such as "0x0e4a" or "0e4a", or an address=value pair such as "7=0x4a"
+pub(crate) fn parse_register(s: &str) -> Result<Register, Box<dyn Error>> {
+    let hex = |s: &str| u16::from_str_radix(s.trim_start_matches("0x"), 16);
+
+    let register = match s.split_once('=') {
+        Some((address, value)) => Register {
+            address: address.parse()?,
+            value: hex(value)?,
+        },
+        None => Register::from_u16(hex(s)?)?,
+    };
+    register.to_u16()?;
+
+    Ok(register)
+}
+
+impl Build {
+    /// The configuration described by the file and flags
+    pub(crate) fn config(&self) -> Result<CodecConfig, Box<dyn Error>> {
+        let mut config = match &self.config {
+            Some(path) => toml::from_str(&fs::read_to_string(path)?)?,
+            None => CodecConfig::new(),
+        };
+
+        if let Some(db) = self.line_in_gain {
+            config.left_line_in.set_gain_db(db)?;
+            config.right_line_in.set_gain_db(db)?;
+        }
+        if let Some(mute) = self.line_in_mute {
+            config.left_line_in.mute = mute;
+            config.right_line_in.mute = mute;
+        }
+        if let Some(volume) = &self.headphone_volume {
+            let volume_db = match volume.as_str() {
+                "mute" => None,
+                db => Some(parse_db(db)?.round() as i16),
+            };
+            config.left_headphone_out.volume_db = volume_db;
+            config.right_headphone_out.volume_db = volume_db;
+        }
+
+        let path = &mut config.analog_audio_path;
+        if let Some(input) = self.input {
+            path.input = match input {
+                InputArg::Line => Input::LineInput,
+                InputArg::Mic => Input::Mic,
+            };
+        }
+        if let Some(mic_boost) = self.mic_boost {
+            path.mic_boost = mic_boost;
+        }
+        if let Some(bypass) = self.bypass {
+            path.bypass = bypass;
+        }
+        if let Some(dac_select) = self.dac_select {
+            path.dac_select = dac_select;
+        }
+
+        let path = &mut config.digital_audio_path;
+        if let Some(deemphasis) = self.deemphasis {
+            path.deemphasis = match deemphasis {
+                DeemphasisArg::Off => Deemphasis::Disabled,
+                DeemphasisArg::Khz32 => Deemphasis::Khz32,
+                DeemphasisArg::Khz441 => Deemphasis::Khz441,
+                DeemphasisArg::Khz48 => Deemphasis::Khz48,
+            };
+        }
+        if let Some(adc_hpf) = self.adc_hpf {
+            path.adc_hpf = adc_hpf;
+        }
+        if let Some(dac_mute) = self.dac_mute {
+            path.dac_mute = dac_mute;
+        }
+
+        if let Some(parts) = &self.power {
+            let power = &mut config.power;
+            power.line_input = parts.contains(&PowerArg::Line);
+            power.mic = parts.contains(&PowerArg::Mic);
+            power.adc = parts.contains(&PowerArg::Adc);
+            power.dac = parts.contains(&PowerArg::Dac);
+            power.output = parts.contains(&PowerArg::Output);
+            power.oscillator = parts.contains(&PowerArg::Oscillator);
+            power.clock_output = parts.contains(&PowerArg::ClockOutput);
+            power.chip = true;
+        }
+
+        if let Some(format) = self.format {
+            config.format.format = match format {
+                FormatArg::I2s => AudioFormat::I2s,
+                FormatArg::Left => AudioFormat::LeftJustified,
+                FormatArg::Right => AudioFormat::RightJustified,
+                FormatArg::Dsp => AudioFormat::Dsp,
+            };
+        }
+        if let Some(bits) = &self.bits {
+            config.format.word_length = match bits.as_str() {
+                "16" => WordLength::Bits16,
+                "20" => WordLength::Bits20,
+                "24" => WordLength::Bits24,
+                _ => WordLength::Bits32,
+            };
+        }
+        if let Some(master) = self.master {
+            config.format.master = master;
+        }
+
+        if let Some(mclk_hz) = self.mclk {
+            config.sampling.mclk_hz = mclk_hz;
+        }
+        if let Some(rate) = self.rate {
+            config.sampling.adc = rate;
+            config.sampling.dac = rate;
+        }
+        if let Some(rate) = self.adc_rate {
+            config.sampling.adc = rate;
+        }
+        if let Some(rate) = self.dac_rate {
+            config.sampling.dac = rate;
+        }
+        if let Some(active) = self.active {
+            config.active = active;
+        }
+
+        Ok(config)
+    }
+
+    /// The steps to print: for `init` the whole power-up sequence, otherwise the registers that
+    /// differ from the reset defaults. Conflicting settings are warned about on stderr.
+    pub(crate) fn steps(&self, init: bool) -> Result<Vec<Step>, Box<dyn Error>> {
+        let config = self.config()?;
+        for warning in warnings(&config)? {
+            eprintln!("warning: {}", warning);
+        }
+
+        Ok(if init {
+            config.init_sequence(self.settle_ms)?.to_vec()
+        } else {
+            config.diff(&CodecConfig::new())?.map(Step::Write).collect()
+        })
+    }
+
+    /// Print the steps in the chosen output format
+    pub(crate) fn print<W: Write>(&self, out: &mut W, steps: &[Step]) -> io::Result<()> {
+        match self.output {
+            Output::Hex => {
+                for step in steps {
+                    match step {
+                        Step::Write(register) => {
+                            writeln!(out, "{:#06x}  {}", word(register)?, register)?
+                        }
+                        Step::Delay { ms } => writeln!(out, "delay   {}ms", ms)?,
+                    }
+                }
+            }
+            Output::C => {
+                // Firmware loops over each array, so the delay splits the writes in two
+                let delays: Vec<u32> = steps
+                    .iter()
+                    .filter_map(|step| match step {
+                        Step::Delay { ms } => Some(*ms),
+                        Step::Write(_) => None,
+                    })
+                    .collect();
+                let mut parts = steps.split(|step| matches!(step, Step::Delay { .. }));
+                match delays.as_slice() {
+                    [] => write_c_array(out, "wm8731_registers", steps)?,
+                    [ms] => {
+                        writeln!(out, "#define WM8731_SETTLE_MS {}", ms)?;
+                        writeln!(out)?;
+                        writeln!(
+                            out,
+                            "/* Write these, then wait WM8731_SETTLE_MS for VMID to settle */"
+                        )?;
+                        write_c_array(out, "wm8731_registers", parts.next().unwrap_or(&[]))?;
+                        writeln!(out)?;
+                        writeln!(out, "/* Write these after the wait */")?;
+                        let settled = parts.next().unwrap_or(&[]);
+                        write_c_array(out, "wm8731_registers_settled", settled)?;
+                    }
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "C output holds at most one delay",
+                        ))
+                    }
+                }
+            }
+            Output::Rust if steps.iter().all(|step| matches!(step, Step::Write(_))) => {
+                writeln!(
+                    out,
+                    "const REGISTERS: [wm8731::Register; {}] = [",
+                    steps.len()
+                )?;
+                for step in steps {
+                    if let Step::Write(register) = step {
+                        writeln!(
+                            out,
+                            "    wm8731::Register {{ address: {}, value: {:#05x} }},",
+                            register.address, register.value
+                        )?;
+                    }
+                }
+                writeln!(out, "];")?;
+            }
+            Output::Rust => {
+                writeln!(
+                    out,
+                    "const STEPS: [wm8731::sequence::Step; {}] = [",
+                    steps.len()
+                )?;
+                for step in steps {
+                    match step {
+                        Step::Write(register) => writeln!(
+                            out,
+                            "    wm8731::sequence::Step::Write(wm8731::Register {{ address: {}, value: {:#05x} }}),",
+                            register.address, register.value
+                        )?,
+                        Step::Delay { ms } => {
+                            writeln!(out, "    wm8731::sequence::Step::Delay {{ ms: {} }},", ms)?
+                        }
+                    }
+                }
+                writeln!(out, "];")?;
+            }
+            Output::I2cset => {
+                for step in steps {
+                    match step {
+                        Step::Write(register) => {
+                            let [high, low] = register.to_bytes().map_err(invalid)?;
+                            writeln!(
+                                out,
+                                "i2cset -y {} {:#04x} {:#04x} {:#04x}",
+                                self.bus, self.address, high, low
+                            )?;
+                        }
+                        Step::Delay { ms } => {
+                            writeln!(out, "sleep {}.{:03}", ms / 1000, ms % 1000)?
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Conflicting settings in `config`, apart from those the codec has after a reset
+///
+/// Bypass is enabled with the line input powered down in the reset defaults, and warning about
+/// that on every run would bury the warnings about settings that were asked for.
+pub(crate) fn warnings(config: &CodecConfig) -> Result<Vec<Warning>, ConfigError> {
+    let defaults: Vec<Warning> = validate(&CodecConfig::new().registers()?).collect();
+
+    Ok(validate(&config.registers()?)
+        .filter(|warning| !defaults.contains(warning))
+        .collect())
+}
+
+/// A C array of the control words written by `steps`
+fn write_c_array<W: Write>(out: &mut W, name: &str, steps: &[Step]) -> io::Result<()> {
+    writeln!(out, "static const uint16_t {}[] = {{", name)?;
+    for step in steps {
+        if let Step::Write(register) = step {
+            writeln!(
+                out,
+                "    {:#06x}, /* R{} */",
+                word(register)?,
+                register.address
+            )?;
+        }
+    }
+    writeln!(out, "}};")
+}
+
+/// The control word for `register`
+fn word(register: &Register) -> io::Result<u16> {
+    register.to_u16().map_err(invalid)
+}
+
+fn invalid<E: Error + Send + Sync + 'static>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Print each word's fields, reporting the words that cannot be parsed on `errors` and carrying on
+/// with the rest
+pub(crate) fn decode_words<W: Write, E: Write>(
+    words: &[&str],
+    out: &mut W,
+    errors: &mut E,
+) -> Result<(), Box<dyn Error>> {
+    let mut failed = 0;
+    for word in words {
+        match parse_register(word) {
+            Ok(register) => match decode(register) {
+                Ok(_) => writeln!(out, "{}", register)?,
+                Err(e) => writeln!(out, "{}  ({})", register, e)?,
+            },
+            Err(e) => {
+                writeln!(errors, "error: `{}`: {}", word, e)?;
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{} of {} words could not be read", failed, words.len()).into());
+    }
+
+    Ok(())
+}
+
+fn run(command: &Command) -> Result<(), Box<dyn Error>> {
+    match command {
+        Command::Build(build) => build.print(&mut io::stdout().lock(), &build.steps(false)?)?,
+        Command::Init(build) => build.print(&mut io::stdout().lock(), &build.steps(true)?)?,
+        Command::Decode { words } => {
+            let mut input = String::new();
+            let words: Vec<&str> = if words.is_empty() {
+                io::stdin().read_to_string(&mut input)?;
+                input.split_whitespace().collect()
+            } else {
+                words.iter().map(String::as_str).collect()
+            };
+            decode_words(&words, &mut io::stdout().lock(), &mut io::stderr().lock())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run the tool with the process's arguments
+pub fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,769 @@
+//! Typed configuration for the whole codec
+//!
+//! [`CodecConfig`] describes every register as plain data, and renders it to register writes
+//! using the [`WM8731`] builders. Two configurations can be compared to find the registers that
+//! need rewriting.
+//!
+//! ```
+//! # use wm8731::config::{CodecConfig, AudioFormat, WordLength};
+//! let mut config = CodecConfig::new();
+//! config.format.format = AudioFormat::LeftJustified;
+//! config.format.word_length = WordLength::Bits16;
+//! config.power.dac = true;
+//! config.power.output = true;
+//! config.power.chip = true;
+//!
+//! for step in config.init_sequence(500).unwrap().iter() {
+//!     // write the register or wait
+//! }
+//!
+//! let mut louder = config;
+//! louder.left_headphone_out.volume_db = Some(3);
+//! assert_eq!(louder.diff(&config).unwrap().count(), 1);
+//! ```
+
+use core::fmt;
+use core::iter::once;
+
+use crate::headphone_out::HeadphoneOut;
+use crate::line_in::{LineIn, Volume as LineInVolume};
+use crate::power_down::PowerDown;
+use crate::rate_solver::{solve, SampleRate};
+use crate::sequence::{self, Step};
+use crate::stereo::BOTH;
+use crate::{ConfigError, GainError, Register, WM8731};
+
+/// Line input settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineInConfig {
+    /// Gain in half-dB steps, from -69 (-34.5dB) to 24 (12dB) in steps of 3 (1.5dB)
+    ///
+    /// Serialized as `gain_db`, the gain in dB.
+    #[cfg_attr(feature = "serde", serde(rename = "gain_db", with = "gain_db"))]
+    pub gain_half_db: i16,
+    pub mute: bool,
+    /// Load the same settings into the other channel
+    pub both: bool,
+}
+
+/// Headphone output settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeadphoneOutConfig {
+    /// Volume from -73dB to 6dB, or `None` to mute
+    pub volume_db: Option<i16>,
+    pub zero_cross_detect: bool,
+    /// Load the same settings into the other channel
+    pub both: bool,
+}
+
+/// Input to the ADC
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Input {
+    LineInput,
+    Mic,
+}
+
+/// Side tone attenuation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SidetoneLevel {
+    #[cfg_attr(feature = "serde", serde(rename = "minus_6db"))]
+    Minus6dB,
+    #[cfg_attr(feature = "serde", serde(rename = "minus_9db"))]
+    Minus9dB,
+    #[cfg_attr(feature = "serde", serde(rename = "minus_12db"))]
+    Minus12dB,
+    #[cfg_attr(feature = "serde", serde(rename = "minus_15db"))]
+    Minus15dB,
+}
+
+/// Analog audio path settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnalogAudioPathConfig {
+    pub mic_boost: bool,
+    pub mute_mic: bool,
+    pub input: Input,
+    pub bypass: bool,
+    pub dac_select: bool,
+    pub sidetone: bool,
+    pub sidetone_attenuation: SidetoneLevel,
+}
+
+/// De-emphasis filter
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Deemphasis {
+    Disabled,
+    Khz32,
+    Khz441,
+    Khz48,
+}
+
+/// Digital audio path settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DigitalAudioPathConfig {
+    pub adc_hpf: bool,
+    pub deemphasis: Deemphasis,
+    pub dac_mute: bool,
+    /// Store the DC offset when the high pass filter is disabled
+    pub store_dc_offset: bool,
+}
+
+/// Which parts of the codec are powered
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PowerConfig {
+    pub line_input: bool,
+    pub mic: bool,
+    pub adc: bool,
+    pub dac: bool,
+    pub output: bool,
+    pub oscillator: bool,
+    pub clock_output: bool,
+    /// The whole chip; when off, the codec is in POWEROFF mode
+    pub chip: bool,
+}
+
+/// Audio data format
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum AudioFormat {
+    RightJustified,
+    LeftJustified,
+    I2s,
+    Dsp,
+}
+
+/// Audio data word length
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum WordLength {
+    Bits16,
+    Bits20,
+    Bits24,
+    Bits32,
+}
+
+/// Digital audio interface settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FormatConfig {
+    pub format: AudioFormat,
+    pub word_length: WordLength,
+    /// In left justified, right justified or I²S modes, right channel data when DACLRC is high;
+    /// in DSP mode, MSB on the second BCLK rising edge
+    pub left_right_phase: bool,
+    /// Right channel DAC data on the left
+    pub left_right_swap: bool,
+    pub master: bool,
+    pub bit_clock_invert: bool,
+}
+
+/// Clocking settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingConfig {
+    /// MCLK frequency; see [`solve`] for the supported values
+    pub mclk_hz: u32,
+    pub adc: SampleRate,
+    pub dac: SampleRate,
+    /// Divide CLKOUT by two
+    pub clock_out_divide_by_two: bool,
+}
+
+/// Settings for every register
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodecConfig {
+    pub left_line_in: LineInConfig,
+    pub right_line_in: LineInConfig,
+    pub left_headphone_out: HeadphoneOutConfig,
+    pub right_headphone_out: HeadphoneOutConfig,
+    pub analog_audio_path: AnalogAudioPathConfig,
+    pub digital_audio_path: DigitalAudioPathConfig,
+    pub power: PowerConfig,
+    pub format: FormatConfig,
+    pub sampling: SamplingConfig,
+    pub active: bool,
+}
+
+impl Input {
+    /// Human-readable name, as printed by `Display`
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Input::LineInput => "line input",
+            Input::Mic => "mic",
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl SidetoneLevel {
+    /// Human-readable name, as printed by `Display`
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            SidetoneLevel::Minus6dB => "-6dB",
+            SidetoneLevel::Minus9dB => "-9dB",
+            SidetoneLevel::Minus12dB => "-12dB",
+            SidetoneLevel::Minus15dB => "-15dB",
+        }
+    }
+}
+
+impl fmt::Display for SidetoneLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Deemphasis {
+    /// Human-readable name, as printed by `Display`
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Deemphasis::Disabled => "disabled",
+            Deemphasis::Khz32 => "32kHz",
+            Deemphasis::Khz441 => "44.1kHz",
+            Deemphasis::Khz48 => "48kHz",
+        }
+    }
+}
+
+impl fmt::Display for Deemphasis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl AudioFormat {
+    /// Human-readable name, as printed by `Display`
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            AudioFormat::RightJustified => "right justified",
+            AudioFormat::LeftJustified => "left justified",
+            AudioFormat::I2s => "I²S",
+            AudioFormat::Dsp => "DSP",
+        }
+    }
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl WordLength {
+    /// Number of bits in each sample
+    pub fn bits(&self) -> u32 {
+        match self {
+            WordLength::Bits16 => 16,
+            WordLength::Bits20 => 20,
+            WordLength::Bits24 => 24,
+            WordLength::Bits32 => 32,
+        }
+    }
+
+    /// Human-readable name, as printed by `Display`
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WordLength::Bits16 => "16 bits",
+            WordLength::Bits20 => "20 bits",
+            WordLength::Bits24 => "24 bits",
+            WordLength::Bits32 => "32 bits",
+        }
+    }
+}
+
+impl fmt::Display for WordLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Convert a line input gain in dB to half-dB steps, checking it is on the 1.5dB grid
+fn half_db_steps(db: f32) -> Result<i16, GainError> {
+    let half_dbs = db * 2.0;
+    // Also rejects NaN
+    if !(f32::from(LineInVolume::MIN_half_dBs)..=f32::from(LineInVolume::MAX_half_dBs))
+        .contains(&half_dbs)
+    {
+        return Err(GainError::OutOfRange);
+    }
+    if half_dbs as i16 as f32 != half_dbs {
+        return Err(GainError::OffGrid);
+    }
+
+    let half_dbs = half_dbs as i16;
+    LineIn::new().volume().try_half_dB_steps(half_dbs)?;
+
+    Ok(half_dbs)
+}
+
+/// Serialize the line input gain in dB rather than half-dB steps
+#[cfg(feature = "serde")]
+mod gain_db {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(half_dbs: &i16, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(f32::from(*half_dbs) / 2.0)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i16, D::Error> {
+        let db = f32::deserialize(deserializer)?;
+        super::half_db_steps(db).map_err(|_| {
+            D::Error::custom("line input gain must be a multiple of 1.5dB from -34.5dB to 12dB")
+        })
+    }
+}
+
+impl LineInConfig {
+    /// Gain in dB
+    pub fn gain_db(&self) -> f32 {
+        f32::from(self.gain_half_db) / 2.0
+    }
+
+    /// Set the gain in dB, which must be a multiple of 1.5dB from -34.5dB to 12dB
+    ///
+    /// Returns [`GainError`] and leaves the gain unchanged if it is not.
+    pub fn set_gain_db(&mut self, db: f32) -> Result<(), GainError> {
+        self.gain_half_db = half_db_steps(db)?;
+
+        Ok(())
+    }
+
+    fn render(&self, address: u8) -> Result<Register, ConfigError> {
+        let mut li = LineIn::new();
+        li.volume().try_half_dB_steps(self.gain_half_db)?;
+        if self.mute {
+            li.mute().enable();
+        } else {
+            li.mute().disable();
+        }
+        if self.both {
+            li.both().enable();
+        } else {
+            li.both().disable();
+        }
+
+        Ok(Register {
+            address,
+            value: li.data,
+        })
+    }
+}
+
+impl HeadphoneOutConfig {
+    fn render(&self, address: u8) -> Result<Register, ConfigError> {
+        let mut ho = HeadphoneOut::new();
+        match self.volume_db {
+            Some(db) => ho.volume().dB(db)?,
+            None => ho.volume().mute(),
+        }
+        if self.zero_cross_detect {
+            ho.zero_cross_detect().enable();
+        } else {
+            ho.zero_cross_detect().disable();
+        }
+        if self.both {
+            ho.both().enable();
+        } else {
+            ho.both().disable();
+        }
+
+        Ok(Register {
+            address,
+            value: ho.data,
+        })
+    }
+}
+
+impl AnalogAudioPathConfig {
+    fn render(&self) -> Register {
+        WM8731::analog_audio_path(|c| {
+            if self.mic_boost {
+                c.mic_boost().enable();
+            } else {
+                c.mic_boost().disable();
+            }
+            if self.mute_mic {
+                c.mute_mic().enable();
+            } else {
+                c.mute_mic().disable();
+            }
+            match self.input {
+                Input::LineInput => c.input_select().line_input(),
+                Input::Mic => c.input_select().mic(),
+            }
+            if self.bypass {
+                c.bypass().enable();
+            } else {
+                c.bypass().disable();
+            }
+            if self.dac_select {
+                c.dac_select().select();
+            } else {
+                c.dac_select().deselect();
+            }
+            if self.sidetone {
+                c.sidetone().enable();
+            } else {
+                c.sidetone().disable();
+            }
+            match self.sidetone_attenuation {
+                SidetoneLevel::Minus6dB => c.sidetone_attenuation().minus_6(),
+                SidetoneLevel::Minus9dB => c.sidetone_attenuation().minus_9(),
+                SidetoneLevel::Minus12dB => c.sidetone_attenuation().minus_12(),
+                SidetoneLevel::Minus15dB => c.sidetone_attenuation().minus_15(),
+            }
+        })
+    }
+}
+
+impl DigitalAudioPathConfig {
+    fn render(&self) -> Register {
+        WM8731::digital_audio_path(|c| {
+            if self.adc_hpf {
+                c.adc_hpf().enable();
+            } else {
+                c.adc_hpf().disable();
+            }
+            match self.deemphasis {
+                Deemphasis::Disabled => c.deemphasis().disable(),
+                Deemphasis::Khz32 => c.deemphasis().frequency_32(),
+                Deemphasis::Khz441 => c.deemphasis().frequency_441(),
+                Deemphasis::Khz48 => c.deemphasis().frequency_48(),
+            }
+            if self.dac_mute {
+                c.dac_mute().enable();
+            } else {
+                c.dac_mute().disable();
+            }
+            if self.store_dc_offset {
+                c.hpor().store();
+            } else {
+                c.hpor().clear();
+            }
+        })
+    }
+}
+
+impl PowerConfig {
+    /// The power down settings, as taken by the [`sequence`] functions and the driver
+    pub fn power_down(&self) -> PowerDown {
+        PowerDown {
+            data: self.render().value,
+        }
+    }
+
+    fn render(&self) -> Register {
+        WM8731::power_down(|c| {
+            if self.line_input {
+                c.line_input().power_on();
+            } else {
+                c.line_input().power_off();
+            }
+            if self.mic {
+                c.mic().power_on();
+            } else {
+                c.mic().power_off();
+            }
+            if self.adc {
+                c.adc().power_on();
+            } else {
+                c.adc().power_off();
+            }
+            if self.dac {
+                c.dac().power_on();
+            } else {
+                c.dac().power_off();
+            }
+            if self.output {
+                c.output().power_on();
+            } else {
+                c.output().power_off();
+            }
+            if self.oscillator {
+                c.oscillator().power_on();
+            } else {
+                c.oscillator().power_off();
+            }
+            if self.clock_output {
+                c.clock_output().power_on();
+            } else {
+                c.clock_output().power_off();
+            }
+            if self.chip {
+                c.power_off().power_on();
+            } else {
+                c.power_off().power_off();
+            }
+        })
+    }
+}
+
+impl FormatConfig {
+    fn render(&self) -> Register {
+        WM8731::digital_audio_interface_format(|c| {
+            match self.format {
+                AudioFormat::RightJustified => c.format().right_justified(),
+                AudioFormat::LeftJustified => c.format().left_justified(),
+                AudioFormat::I2s => c.format().i2s(),
+                AudioFormat::Dsp => c.format().dsp(),
+            }
+            match self.word_length {
+                WordLength::Bits16 => c.bit_length().bits_16(),
+                WordLength::Bits20 => c.bit_length().bits_20(),
+                WordLength::Bits24 => c.bit_length().bits_24(),
+                WordLength::Bits32 => c.bit_length().bits_32(),
+            }
+            if self.left_right_phase {
+                c.left_right_phase().data_when_daclrc_high();
+            } else {
+                c.left_right_phase().data_when_daclrc_low();
+            }
+            if self.left_right_swap {
+                c.left_right_dac_clock_swap().right_channel_dac_data_left();
+            } else {
+                c.left_right_dac_clock_swap().right_channel_dac_data_right();
+            }
+            if self.master {
+                c.master_slave().master();
+            } else {
+                c.master_slave().slave();
+            }
+            if self.bit_clock_invert {
+                c.bit_clock_invert().invert();
+            } else {
+                c.bit_clock_invert().no_invert();
+            }
+        })
+    }
+}
+
+impl SamplingConfig {
+    fn render(&self) -> Result<Register, ConfigError> {
+        let sampling = solve(self.mclk_hz, self.adc, self.dac)?;
+
+        Ok(WM8731::sampling(|c| {
+            *c = sampling;
+            if self.clock_out_divide_by_two {
+                c.clock_out_divider_select().divided_by_two();
+            } else {
+                c.clock_out_divider_select().normal();
+            }
+        }))
+    }
+}
+
+impl CodecConfig {
+    /// The configuration the codec has after a reset, with a 12.288MHz MCLK
+    pub fn new() -> Self {
+        let line_in = LineInConfig {
+            gain_half_db: 0,
+            mute: true,
+            both: false,
+        };
+        let headphone_out = HeadphoneOutConfig {
+            volume_db: Some(0),
+            zero_cross_detect: false,
+            both: false,
+        };
+
+        CodecConfig {
+            left_line_in: line_in,
+            right_line_in: line_in,
+            left_headphone_out: headphone_out,
+            right_headphone_out: headphone_out,
+            analog_audio_path: AnalogAudioPathConfig {
+                mic_boost: false,
+                mute_mic: true,
+                input: Input::LineInput,
+                bypass: true,
+                dac_select: false,
+                sidetone: false,
+                sidetone_attenuation: SidetoneLevel::Minus6dB,
+            },
+            digital_audio_path: DigitalAudioPathConfig {
+                adc_hpf: true,
+                deemphasis: Deemphasis::Disabled,
+                dac_mute: true,
+                store_dc_offset: false,
+            },
+            power: PowerConfig {
+                line_input: false,
+                mic: false,
+                adc: false,
+                dac: false,
+                output: false,
+                oscillator: true,
+                clock_output: true,
+                chip: false,
+            },
+            format: FormatConfig {
+                format: AudioFormat::I2s,
+                word_length: WordLength::Bits24,
+                left_right_phase: false,
+                left_right_swap: false,
+                master: false,
+                bit_clock_invert: false,
+            },
+            sampling: SamplingConfig {
+                mclk_hz: 12_288_000,
+                adc: SampleRate::Hz48000,
+                dac: SampleRate::Hz48000,
+                clock_out_divide_by_two: false,
+            },
+            active: false,
+        }
+    }
+
+    /// Registers 0-9 in address order
+    pub fn registers(&self) -> Result<[Register; 10], ConfigError> {
+        Ok([
+            self.left_line_in.render(0)?,
+            self.right_line_in.render(1)?,
+            self.left_headphone_out.render(2)?,
+            self.right_headphone_out.render(3)?,
+            self.analog_audio_path.render(),
+            self.digital_audio_path.render(),
+            self.power.render(),
+            self.format.render(),
+            self.sampling.render()?,
+            if self.active {
+                WM8731::active().active()
+            } else {
+                WM8731::active().inactive()
+            },
+        ])
+    }
+
+    /// The registers that [`sequence::power_up`] writes between powering up and activating:
+    /// every register but power down and active, in address order
+    pub fn power_up_configuration(&self) -> Result<[Register; 8], ConfigError> {
+        let registers = self.registers()?;
+
+        Ok([
+            registers[0],
+            registers[1],
+            registers[2],
+            registers[3],
+            registers[4],
+            registers[5],
+            registers[7],
+            registers[8],
+        ])
+    }
+
+    /// Every step needed to bring the codec from any state to this configuration
+    ///
+    /// The codec is reset, then set up by [`sequence::power_up`] so the outputs are enabled last,
+    /// `settle_ms` after the interface is activated. If `active` is not set, the interface is
+    /// left inactive instead.
+    pub fn init_sequence(&self, settle_ms: u32) -> Result<[Step; 13], ConfigError> {
+        let registers = self.registers()?;
+        let power = self.power.power_down();
+        let configuration = self.power_up_configuration()?;
+
+        let mut steps = [Step::Delay { ms: 0 }; 13];
+        let sequence = once(Step::Write(WM8731::reset()))
+            .chain(sequence::power_up(power, &configuration, settle_ms))
+            .map(|step| match step {
+                Step::Write(register) if register.address == registers[9].address => {
+                    Step::Write(registers[9])
+                }
+                step => step,
+            });
+        for (slot, step) in steps.iter_mut().zip(sequence) {
+            *slot = step;
+        }
+
+        Ok(steps)
+    }
+
+    /// Registers to write to change the codec from `previous` to this configuration
+    ///
+    /// These are the registers that differ, in address order, with two additions. A line input
+    /// or headphone out write with its "both" bit set also loads the other channel, so the other
+    /// channel is written again after it. The digital audio interface format and sampling
+    /// registers may only change while the interface is inactive, so if they differ and
+    /// `previous` is active, the interface is deactivated first and, if this configuration is
+    /// active, reactivated after them.
+    pub fn diff(
+        &self,
+        previous: &CodecConfig,
+    ) -> Result<impl Iterator<Item = Register>, ConfigError> {
+        let registers = self.registers()?;
+        let previous_registers = previous.registers()?;
+        let changed = |address: usize| registers[address] != previous_registers[address];
+
+        let mut writes = [WM8731::reset(); 13];
+        let mut len = 0;
+        let mut push = |register: Register| {
+            writes[len] = register;
+            len += 1;
+        };
+
+        for left in [0, 2] {
+            let right = left + 1;
+            let spills = |address: usize| changed(address) && registers[address].value & BOTH != 0;
+            if spills(right) && !spills(left) {
+                push(registers[right]);
+                push(registers[left]);
+            } else {
+                if changed(left) {
+                    push(registers[left]);
+                }
+                if changed(right) || spills(left) {
+                    push(registers[right]);
+                }
+            }
+        }
+        for address in [4, 5, 6] {
+            if changed(address) {
+                push(registers[address]);
+            }
+        }
+        let deactivate = (changed(7) || changed(8)) && previous.active;
+        if deactivate {
+            push(WM8731::active().inactive());
+        }
+        for address in [7, 8] {
+            if changed(address) {
+                push(registers[address]);
+            }
+        }
+        if (deactivate && self.active) || (!deactivate && changed(9)) {
+            push(registers[9]);
+        }
+
+        Ok(IntoIterator::into_iter(writes).take(len))
+    }
+}
@@ -0,0 +1,247 @@
+//! Turn raw register writes back into typed settings
+//!
+//! Useful for making sense of control bus captures. Each register decodes into the same
+//! settings types used by [`CodecConfig`](crate::config::CodecConfig), except for the sampling
+//! control register, which cannot say which MCLK it was meant for and decodes into
+//! [`SamplingSettings`].
+//!
+//! ```
+//! # use wm8731::decode::{decode, Decoded};
+//! # use wm8731::config::{AudioFormat, WordLength};
+//! # use wm8731::Register;
+//! let register = Register { address: 7, value: 0b0_1100_1010 };
+//! match decode(register).unwrap() {
+//!     Decoded::DigitalAudioInterfaceFormat(format) => {
+//!         assert_eq!(format.format, AudioFormat::I2s);
+//!         assert_eq!(format.word_length, WordLength::Bits24);
+//!         assert!(format.master);
+//!         assert!(format.bit_clock_invert);
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! ```
+
+use crate::config::{
+    AnalogAudioPathConfig, AudioFormat, Deemphasis, DigitalAudioPathConfig, FormatConfig,
+    HeadphoneOutConfig, Input, LineInConfig, PowerConfig, SidetoneLevel, WordLength,
+};
+use crate::rate_solver::SampleRate;
+use crate::{DecodeError, Register};
+
+/// Sampling control settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplingSettings {
+    /// USB mode (12MHz MCLK) rather than normal mode
+    pub usb: bool,
+    /// Base over-sampling rate: 272fs in USB mode or 384fs in normal mode when set, 250fs or
+    /// 256fs otherwise
+    pub bosr: bool,
+    pub adc: SampleRate,
+    pub dac: SampleRate,
+    pub core_clock_divide_by_two: bool,
+    pub clock_out_divide_by_two: bool,
+}
+
+/// Settings held by one register
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Decoded {
+    LeftLineIn(LineInConfig),
+    RightLineIn(LineInConfig),
+    LeftHeadphoneOut(HeadphoneOutConfig),
+    RightHeadphoneOut(HeadphoneOutConfig),
+    AnalogAudioPath(AnalogAudioPathConfig),
+    DigitalAudioPath(DigitalAudioPathConfig),
+    PowerDown(PowerConfig),
+    DigitalAudioInterfaceFormat(FormatConfig),
+    Sampling(SamplingSettings),
+    Active(bool),
+    Reset,
+}
+
+fn bit(value: u16, index: u16) -> bool {
+    value & (1 << index) != 0
+}
+
+fn field(value: u16, index: u16, length: u16) -> u16 {
+    (value >> index) & ((1 << length) - 1)
+}
+
+/// Make sure only the bits in `used` are set
+fn check_reserved(register: Register, used: u16) -> Result<(), DecodeError> {
+    if register.value & !used != 0 {
+        return Err(DecodeError::ReservedBits {
+            address: register.address,
+            value: register.value,
+        });
+    }
+
+    Ok(())
+}
+
+pub(crate) fn line_in(value: u16) -> LineInConfig {
+    LineInConfig {
+        gain_half_db: field(value, 0, 5) as i16 * 3 - 69,
+        mute: bit(value, 7),
+        both: bit(value, 8),
+    }
+}
+
+pub(crate) fn headphone_out(value: u16) -> HeadphoneOutConfig {
+    let volume = field(value, 0, 7) as i16;
+
+    HeadphoneOutConfig {
+        // Anything below 0b011_0000 mutes
+        volume_db: if volume < 0b011_0000 {
+            None
+        } else {
+            Some(volume - 0b111_1001)
+        },
+        zero_cross_detect: bit(value, 7),
+        both: bit(value, 8),
+    }
+}
+
+pub(crate) fn analog_audio_path(value: u16) -> AnalogAudioPathConfig {
+    AnalogAudioPathConfig {
+        mic_boost: bit(value, 0),
+        mute_mic: bit(value, 1),
+        input: if bit(value, 2) {
+            Input::Mic
+        } else {
+            Input::LineInput
+        },
+        bypass: bit(value, 3),
+        dac_select: bit(value, 4),
+        sidetone: bit(value, 5),
+        sidetone_attenuation: match field(value, 6, 2) {
+            0b00 => SidetoneLevel::Minus6dB,
+            0b01 => SidetoneLevel::Minus9dB,
+            0b10 => SidetoneLevel::Minus12dB,
+            _ => SidetoneLevel::Minus15dB,
+        },
+    }
+}
+
+pub(crate) fn digital_audio_path(value: u16) -> DigitalAudioPathConfig {
+    DigitalAudioPathConfig {
+        // ADCHPD disables the filter
+        adc_hpf: !bit(value, 0),
+        deemphasis: match field(value, 1, 2) {
+            0b00 => Deemphasis::Disabled,
+            0b01 => Deemphasis::Khz32,
+            0b10 => Deemphasis::Khz441,
+            _ => Deemphasis::Khz48,
+        },
+        dac_mute: bit(value, 3),
+        store_dc_offset: bit(value, 4),
+    }
+}
+
+pub(crate) fn power_down(value: u16) -> PowerConfig {
+    // Each bit powers its part down
+    PowerConfig {
+        line_input: !bit(value, 0),
+        mic: !bit(value, 1),
+        adc: !bit(value, 2),
+        dac: !bit(value, 3),
+        output: !bit(value, 4),
+        oscillator: !bit(value, 5),
+        clock_output: !bit(value, 6),
+        chip: !bit(value, 7),
+    }
+}
+
+pub(crate) fn digital_audio_interface_format(value: u16) -> FormatConfig {
+    FormatConfig {
+        format: match field(value, 0, 2) {
+            0b00 => AudioFormat::RightJustified,
+            0b01 => AudioFormat::LeftJustified,
+            0b10 => AudioFormat::I2s,
+            _ => AudioFormat::Dsp,
+        },
+        word_length: match field(value, 2, 2) {
+            0b00 => WordLength::Bits16,
+            0b01 => WordLength::Bits20,
+            0b10 => WordLength::Bits24,
+            _ => WordLength::Bits32,
+        },
+        left_right_phase: bit(value, 4),
+        left_right_swap: bit(value, 5),
+        master: bit(value, 6),
+        bit_clock_invert: bit(value, 7),
+    }
+}
+
+pub(crate) fn sampling(value: u16) -> Result<SamplingSettings, DecodeError> {
+    use SampleRate::*;
+
+    let usb = bit(value, 0);
+    // 8kHz derived from a 44.1kHz-family clock
+    let low = if usb { Hz8021 } else { Hz8018 };
+    let code = field(value, 2, 4) as u8;
+    let (adc, dac) = match code {
+        0b0000 => (Hz48000, Hz48000),
+        0b0001 => (Hz48000, Hz8000),
+        0b0010 => (Hz8000, Hz48000),
+        0b0011 => (Hz8000, Hz8000),
+        0b0110 => (Hz32000, Hz32000),
+        0b0111 => (Hz96000, Hz96000),
+        0b1000 => (Hz44100, Hz44100),
+        0b1001 => (Hz44100, low),
+        0b1010 => (low, Hz44100),
+        0b1011 => (low, low),
+        0b1111 => (Hz88200, Hz88200),
+        _ => return Err(DecodeError::ReservedSampleRate(code)),
+    };
+
+    Ok(SamplingSettings {
+        usb,
+        bosr: bit(value, 1),
+        adc,
+        dac,
+        core_clock_divide_by_two: bit(value, 6),
+        clock_out_divide_by_two: bit(value, 7),
+    })
+}
+
+/// Decode a register into its settings
+///
+/// Fails if the register could not have been written to the codec, if any reserved bit is set,
+/// or if the sample rate code is not one of those in the datasheet.
+pub fn decode(register: Register) -> Result<Decoded, DecodeError> {
+    register.to_u16()?;
+    let value = register.value;
+
+    let used = match register.address {
+        4 | 6 | 7 | 8 => 0b0_1111_1111,
+        5 => 0b0_0001_1111,
+        0 | 1 => 0b1_1001_1111,
+        9 => 0b0_0000_0001,
+        _ => 0b1_1111_1111,
+    };
+    check_reserved(register, used)?;
+
+    Ok(match register.address {
+        0 => Decoded::LeftLineIn(line_in(value)),
+        1 => Decoded::RightLineIn(line_in(value)),
+        2 => Decoded::LeftHeadphoneOut(headphone_out(value)),
+        3 => Decoded::RightHeadphoneOut(headphone_out(value)),
+        4 => Decoded::AnalogAudioPath(analog_audio_path(value)),
+        5 => Decoded::DigitalAudioPath(digital_audio_path(value)),
+        6 => Decoded::PowerDown(power_down(value)),
+        7 => Decoded::DigitalAudioInterfaceFormat(digital_audio_interface_format(value)),
+        8 => Decoded::Sampling(sampling(value)?),
+        9 => Decoded::Active(bit(value, 0)),
+        _ => Decoded::Reset,
+    })
+}
+
+/// Decode a 16-bit control word into its settings
+pub fn decode_u16(word: u16) -> Result<Decoded, DecodeError> {
+    decode(Register::from_u16(word)?)
+}
@@ -1,6 +1,10 @@
 //! Configuration for the digital audio interface
 
+use core::fmt;
+
 use crate::bitmask::BitMask;
+use crate::decode;
+use crate::display::{bit_clock_invert_str, master_str, phase_str, Enabled, Plain};
 
 pub struct LeftRight<'a> {
     index: u16,
@@ -177,7 +181,7 @@ impl<'a> BitLength<'a> {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Copy, Clone)]
 pub struct DigitalAudioInterfaceFormat {
     pub(crate) data: u16,
 }
@@ -190,32 +194,75 @@ impl DigitalAudioInterfaceFormat {
     }
 
     /// Audio data format select
-    pub fn format(&mut self) -> Format {
+    pub fn format(&mut self) -> Format<'_> {
         Format::new(0, &mut self.data)
     }
 
     /// Input audio data bit length select
-    pub fn bit_length(&mut self) -> BitLength {
+    pub fn bit_length(&mut self) -> BitLength<'_> {
         BitLength::new(2, &mut self.data)
     }
 
     /// DACLRC phase control (in left, right, or I²S modes)
-    pub fn left_right_phase(&mut self) -> LeftRightPhase {
+    pub fn left_right_phase(&mut self) -> LeftRightPhase<'_> {
         LeftRightPhase::new(4, &mut self.data)
     }
 
     /// DAC left/right clock swap
-    pub fn left_right_dac_clock_swap(&mut self) -> ClockSwap {
+    pub fn left_right_dac_clock_swap(&mut self) -> ClockSwap<'_> {
         ClockSwap::new(5, &mut self.data)
     }
 
     /// Master slave mode control
-    pub fn master_slave(&mut self) -> MasterSlave {
+    pub fn master_slave(&mut self) -> MasterSlave<'_> {
         MasterSlave::new(6, &mut self.data)
     }
 
     /// Bit clock invert
-    pub fn bit_clock_invert(&mut self) -> Invert {
+    pub fn bit_clock_invert(&mut self) -> Invert<'_> {
         Invert::new(7, &mut self.data)
     }
 }
+
+impl fmt::Debug for DigitalAudioInterfaceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let daif = decode::digital_audio_interface_format(self.data);
+
+        f.debug_struct("DigitalAudioInterfaceFormat")
+            .field("format", &Plain(daif.format))
+            .field("bit_length", &Plain(daif.word_length))
+            .field("left_right_phase", &Plain(phase_str(daif.left_right_phase)))
+            .field("left_right_dac_clock_swap", &Enabled(daif.left_right_swap))
+            .field("master_slave", &Plain(master_str(daif.master)))
+            .field(
+                "bit_clock_invert",
+                &Plain(bit_clock_invert_str(daif.bit_clock_invert)),
+            )
+            .finish()
+    }
+}
+
+impl fmt::Display for DigitalAudioInterfaceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DigitalAudioInterfaceFormat {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let daif = decode::digital_audio_interface_format(self.data);
+
+        defmt::write!(
+            f,
+            "DigitalAudioInterfaceFormat {{ format: {=str}, bit_length: {=str}, left_right_phase: {=str}, \
+             left_right_dac_clock_swap: {}, master_slave: {=str}, bit_clock_invert: {=str} }}",
+            daif.format.as_str(),
+            daif.word_length.as_str(),
+            phase_str(daif.left_right_phase),
+            Enabled(daif.left_right_swap),
+            master_str(daif.master),
+            bit_clock_invert_str(daif.bit_clock_invert)
+        )
+    }
+}
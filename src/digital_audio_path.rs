@@ -1,6 +1,10 @@
 //! Configuration for digital audio path
 
+use core::fmt;
+
 use crate::bitmask::BitMask;
+use crate::decode;
+use crate::display::{hpor_str, Enabled, Plain};
 use crate::EnableDisable;
 
 pub struct Deemphasis<'a> {
@@ -72,7 +76,7 @@ impl<'a> AdcHpf<'a> {
         self.bitmask.set(self.index);
     }
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Copy, Clone)]
 pub struct DigitalAudioPath {
     pub(crate) data: u16,
 }
@@ -87,22 +91,57 @@ impl DigitalAudioPath {
     /// Enable or disable ADC high pass filter.
     ///
     /// Under the hood, ADC high pass filter is enabled by setting the ADCHPD bit to zero.    
-    pub fn adc_hpf(&mut self) -> AdcHpf {
+    pub fn adc_hpf(&mut self) -> AdcHpf<'_> {
         AdcHpf::new(0, &mut self.data)
     }
 
     /// De-emphasis control
-    pub fn deemphasis(&mut self) -> Deemphasis {
+    pub fn deemphasis(&mut self) -> Deemphasis<'_> {
         Deemphasis::new(1, &mut self.data)
     }
 
     /// DAC soft mute control
-    pub fn dac_mute(&mut self) -> EnableDisable {
+    pub fn dac_mute(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(3, &mut self.data)
     }
 
     /// Store DC offset when high pass filter disabled
-    pub fn hpor(&mut self) -> HpfDc {
+    pub fn hpor(&mut self) -> HpfDc<'_> {
         HpfDc::new(4, &mut self.data)
     }
 }
+
+impl fmt::Debug for DigitalAudioPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dap = decode::digital_audio_path(self.data);
+
+        f.debug_struct("DigitalAudioPath")
+            .field("adc_hpf", &Enabled(dap.adc_hpf))
+            .field("deemphasis", &Plain(dap.deemphasis))
+            .field("dac_mute", &Enabled(dap.dac_mute))
+            .field("hpor", &Plain(hpor_str(dap.store_dc_offset)))
+            .finish()
+    }
+}
+
+impl fmt::Display for DigitalAudioPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DigitalAudioPath {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let dap = decode::digital_audio_path(self.data);
+
+        defmt::write!(
+            f,
+            "DigitalAudioPath {{ adc_hpf: {}, deemphasis: {=str}, dac_mute: {}, hpor: {=str} }}",
+            Enabled(dap.adc_hpf),
+            dap.deemphasis.as_str(),
+            Enabled(dap.dac_mute),
+            hpor_str(dap.store_dc_offset)
+        )
+    }
+}
@@ -0,0 +1,170 @@
+//! Adapters for printing register fields by meaning
+
+use core::fmt;
+
+// Field values shared by the `Debug` and `defmt::Format` impls of the register builders
+
+/// A muted output
+pub(crate) const MUTED: &str = "muted";
+
+/// DACSEL
+pub(crate) fn dac_select_str(selected: bool) -> &'static str {
+    if selected {
+        "selected"
+    } else {
+        "deselected"
+    }
+}
+
+/// HPOR
+pub(crate) fn hpor_str(store: bool) -> &'static str {
+    if store {
+        "store"
+    } else {
+        "clear"
+    }
+}
+
+/// LRP
+pub(crate) fn phase_str(right_when_high: bool) -> &'static str {
+    if right_when_high {
+        "right channel when DACLRC high"
+    } else {
+        "right channel when DACLRC low"
+    }
+}
+
+/// MS
+pub(crate) fn master_str(master: bool) -> &'static str {
+    if master {
+        "master"
+    } else {
+        "slave"
+    }
+}
+
+/// BCLKINV
+pub(crate) fn bit_clock_invert_str(inverted: bool) -> &'static str {
+    if inverted {
+        "inverted"
+    } else {
+        "not inverted"
+    }
+}
+
+/// USB/NORMAL
+pub(crate) fn usb_normal_str(usb: bool) -> &'static str {
+    if usb {
+        "USB"
+    } else {
+        "normal"
+    }
+}
+
+/// BOSR, which depends on the mode
+pub(crate) fn oversampling_str(usb: bool, bosr: bool) -> &'static str {
+    match (usb, bosr) {
+        (true, true) => "272fs",
+        (true, false) => "250fs",
+        (false, true) => "384fs",
+        (false, false) => "256fs",
+    }
+}
+
+/// CLKIDIV2 and CLKODIV2
+pub(crate) fn divider_str(divided: bool) -> &'static str {
+    if divided {
+        "MCLK/2"
+    } else {
+        "MCLK"
+    }
+}
+
+/// Prints "on" or "off"
+pub(crate) struct OnOff(pub bool);
+
+impl OnOff {
+    fn as_str(&self) -> &'static str {
+        if self.0 {
+            "on"
+        } else {
+            "off"
+        }
+    }
+}
+
+impl fmt::Debug for OnOff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Prints "enabled" or "disabled"
+pub(crate) struct Enabled(pub bool);
+
+impl Enabled {
+    fn as_str(&self) -> &'static str {
+        if self.0 {
+            "enabled"
+        } else {
+            "disabled"
+        }
+    }
+}
+
+impl fmt::Debug for Enabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Prints a gain given in half-dB steps, such as "-34.5dB"
+pub(crate) struct HalfDb(pub i16);
+
+impl HalfDb {
+    /// Sign, whole dBs and fraction
+    fn parts(&self) -> (&'static str, u16, &'static str) {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let half_dbs = self.0.unsigned_abs();
+        let half = if half_dbs % 2 == 1 { ".5" } else { "" };
+        (sign, half_dbs / 2, half)
+    }
+}
+
+impl fmt::Debug for HalfDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (sign, whole, half) = self.parts();
+        write!(f, "{}{}{}dB", sign, whole, half)
+    }
+}
+
+/// Prints the `Display` form of a value where `Debug` is expected
+pub(crate) struct Plain<T>(pub T);
+
+impl<T: fmt::Display> fmt::Debug for Plain<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for OnOff {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Enabled {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for HalfDb {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let (sign, whole, half) = self.parts();
+        defmt::write!(f, "{=str}{=u16}{=str}dB", sign, whole, half)
+    }
+}
@@ -0,0 +1,497 @@
+//! Blocking driver for the control interface
+//!
+//! The [`WM8731`] builders only describe register contents; [`Wm8731`] owns an I²C bus or SPI
+//! device and writes those registers to the codec.
+//!
+//! ```
+//! # use embedded_hal::i2c::{ErrorType, I2c, Operation, SevenBitAddress};
+//! # struct Bus;
+//! # impl ErrorType for Bus { type Error = core::convert::Infallible; }
+//! # impl I2c for Bus {
+//! #     fn transaction(&mut self, _: SevenBitAddress, _: &mut [Operation<'_>]) -> Result<(), Self::Error> { Ok(()) }
+//! # }
+//! # let i2c = Bus;
+//! use wm8731::driver::Wm8731;
+//!
+//! let mut codec = Wm8731::new(i2c);
+//! codec.reset()?;
+//! codec.power_down(|c| {
+//!     c.line_input().power_on();
+//!     c.adc().power_on();
+//! })?;
+//! let mut codec = codec.activate().map_err(|(_codec, e)| e)?;
+//! codec.left_line_in(|c| c.mute().disable())?;
+//! # Ok::<(), wm8731::driver::Error<core::convert::Infallible>>(())
+//! ```
+//!
+//! Boards strapped for SPI control use [`Wm8731::new_spi`] instead; everything else is the same.
+//!
+//! The datasheet only allows the digital audio interface format and sampling control registers
+//! to change while the interface is inactive. The driver tracks this in its type: it starts out
+//! [`Inactive`], [`activate`](Wm8731::activate) turns it into a driver in the [`Active`] state
+//! that no longer offers those writes, and [`deactivate`](Wm8731::deactivate) turns it back.
+//! The power sequences that change the state are run the same way, with
+//! [`run_power_up`](Wm8731::run_power_up), [`run_power_down`](Wm8731::run_power_down) and
+//! [`run_standby`](Wm8731::run_standby). [`write`](Wm8731::write) and [`run`](Wm8731::run)
+//! reject registers that would bypass the state with [`Error::State`]. A transition that fails
+//! hands the driver back along with the error, so it can be retried or released.
+//!
+//! ```compile_fail
+//! # use embedded_hal::i2c::{ErrorType, I2c, Operation, SevenBitAddress};
+//! # struct Bus;
+//! # impl ErrorType for Bus { type Error = core::convert::Infallible; }
+//! # impl I2c for Bus {
+//! #     fn transaction(&mut self, _: SevenBitAddress, _: &mut [Operation<'_>]) -> Result<(), Self::Error> { Ok(()) }
+//! # }
+//! # let i2c = Bus;
+//! # use wm8731::driver::Wm8731;
+//! let mut codec = Wm8731::new(i2c).activate().map_err(|(_codec, e)| e)?;
+//! // Sampling can only change while inactive
+//! codec.sampling(|c| c.sample_rate().adc_48().dac_48())?;
+//! # Ok::<(), wm8731::driver::Error<core::convert::Infallible>>(())
+//! ```
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{ErrorKind, I2c, NoAcknowledgeSource};
+use embedded_hal::spi::SpiDevice;
+
+use crate::analog_audio_path::AnalogAudioPath;
+use crate::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+use crate::digital_audio_path::DigitalAudioPath;
+use crate::headphone_out::HeadphoneOut;
+use crate::interface::{I2cInterface, Interface, SpiInterface};
+use crate::line_in::LineIn;
+use crate::power_down::PowerDown;
+use crate::sampling::Sampling;
+use crate::sequence::{self, Step};
+use crate::{Register, RegisterError, WM8731};
+
+/// I²C address when the CSB pin is tied low
+pub const ADDRESS_CSB_LOW: u8 = 0b001_1010;
+
+/// I²C address when the CSB pin is tied high
+pub const ADDRESS_CSB_HIGH: u8 = 0b001_1011;
+
+/// Errors returned when writing to the codec
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// The codec did not acknowledge the transfer (I²C only)
+    Nack(NoAcknowledgeSource),
+    /// Any other error reported by the bus
+    Bus(E),
+    /// The register cannot be encoded, so nothing was sent
+    Register(RegisterError),
+    /// The register at this address cannot be written in the driver's current state, so nothing
+    /// was sent
+    State(u8),
+}
+
+impl<E: embedded_hal::i2c::Error> Error<E> {
+    /// Classify an I²C bus error
+    pub(crate) fn from_i2c(e: E) -> Self {
+        match e.kind() {
+            ErrorKind::NoAcknowledge(source) => Error::Nack(source),
+            _ => Error::Bus(e),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Nack(source) => f.write_str(nack_str(*source)),
+            Error::Bus(e) => write!(f, "bus error: {}", e),
+            Error::Register(e) => write!(f, "{}", e),
+            Error::State(address) => {
+                write!(f, "R{} cannot be written in this interface state", address)
+            }
+        }
+    }
+}
+
+/// What a missing acknowledge means for the codec
+pub(crate) fn nack_str(source: NoAcknowledgeSource) -> &'static str {
+    match source {
+        NoAcknowledgeSource::Address => "the codec did not acknowledge its address",
+        NoAcknowledgeSource::Data => "the codec did not acknowledge the control word",
+        NoAcknowledgeSource::Unknown => "the codec did not acknowledge the transfer",
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug + fmt::Display> std::error::Error for Error<E> {}
+
+impl<E> From<RegisterError> for Error<E> {
+    fn from(e: RegisterError) -> Self {
+        Error::Register(e)
+    }
+}
+
+/// Address of the power down register
+pub(crate) const POWER_DOWN: u8 = 6;
+
+/// Address of the digital audio interface format register
+const FORMAT: u8 = 7;
+
+/// Address of the sampling control register
+const SAMPLING: u8 = 8;
+
+/// Address of the active control register
+pub(crate) const ACTIVE: u8 = 9;
+
+/// Address of the reset register
+pub(crate) const RESET: u8 = 15;
+
+/// The digital audio interface is inactive, so every register can be written
+pub struct Inactive;
+
+/// The digital audio interface is active
+pub struct Active;
+
+mod sealed {
+    pub trait Sealed {
+        /// Whether the register at `address` can be written without a state transition
+        fn writable(address: u8) -> bool;
+    }
+}
+
+/// State of the digital audio interface, [`Inactive`] or [`Active`]
+pub trait State: sealed::Sealed {}
+
+impl sealed::Sealed for Inactive {
+    fn writable(address: u8) -> bool {
+        address != ACTIVE
+    }
+}
+
+impl State for Inactive {}
+
+impl sealed::Sealed for Active {
+    fn writable(address: u8) -> bool {
+        !matches!(address, FORMAT | SAMPLING | ACTIVE | RESET)
+    }
+}
+
+impl State for Active {}
+
+/// Check that `register` can be written in state `S`
+pub(crate) fn check_state<S: State, E>(register: Register) -> Result<(), Error<E>> {
+    if S::writable(register.address) {
+        Ok(())
+    } else {
+        Err(Error::State(register.address))
+    }
+}
+
+/// WM8731 driver over a blocking control [`Interface`]
+///
+/// The codec starts out inactive after power on or reset, which the driver assumes when it is
+/// created.
+pub struct Wm8731<I, S = Inactive> {
+    interface: I,
+    state: PhantomData<S>,
+}
+
+impl<I2C: I2c> Wm8731<I2cInterface<I2C>> {
+    /// Create a driver for a codec on an I²C bus with the CSB pin tied low
+    pub fn new(i2c: I2C) -> Self {
+        Self::new_with_address(i2c, ADDRESS_CSB_LOW)
+    }
+
+    /// Create a driver for a codec at the given 7-bit I²C address
+    pub fn new_with_address(i2c: I2C, address: u8) -> Self {
+        Wm8731 {
+            interface: I2cInterface::new(i2c, address),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<I2C: I2c, S> Wm8731<I2cInterface<I2C>, S> {
+    /// Give back the I²C bus
+    pub fn release(self) -> I2C {
+        self.interface.release()
+    }
+}
+
+impl<SPI: SpiDevice> Wm8731<SpiInterface<SPI>> {
+    /// Create a driver for a codec on a 3-wire SPI control interface
+    pub fn new_spi(spi: SPI) -> Self {
+        Wm8731 {
+            interface: SpiInterface::new(spi),
+            state: PhantomData,
+        }
+    }
+}
+
+impl<SPI: SpiDevice, S> Wm8731<SpiInterface<SPI>, S> {
+    /// Give back the SPI device
+    pub fn release(self) -> SPI {
+        self.interface.release()
+    }
+}
+
+impl<I: Interface, S> Wm8731<I, S> {
+    /// The same driver in another state
+    fn into_state<T>(self) -> Wm8731<I, T> {
+        Wm8731 {
+            interface: self.interface,
+            state: PhantomData,
+        }
+    }
+
+    /// Perform each step in order, without checking the state
+    fn run_steps<T: IntoIterator<Item = Step>, D: DelayNs>(
+        &mut self,
+        steps: T,
+        delay: &mut D,
+    ) -> Result<(), I::Error> {
+        for step in steps {
+            match step {
+                Step::Write(register) => self.interface.write(register)?,
+                Step::Delay { ms } => delay.delay_ms(ms),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write `register`, then move to state `T`; on failure, give back the driver unchanged
+    fn transition<T>(mut self, register: Register) -> Result<Wm8731<I, T>, (Self, I::Error)> {
+        match self.interface.write(register) {
+            Ok(()) => Ok(self.into_state()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I, E, S> Wm8731<I, S>
+where
+    I: Interface<Error = Error<E>>,
+    S: State,
+{
+    /// Write a register to the codec
+    ///
+    /// Registers that would change the state or that the state does not allow are rejected
+    /// with [`Error::State`]: the active and reset registers are only written by the state
+    /// transitions, and the digital audio interface format and sampling registers only while
+    /// [`Inactive`].
+    pub fn write(&mut self, register: Register) -> Result<(), I::Error> {
+        check_state::<S, E>(register)?;
+        self.interface.write(register)
+    }
+
+    /// Perform each step of a sequence in order
+    ///
+    /// Every write is checked as by [`write`](Self::write) before any is made, so a sequence
+    /// with a register the state does not allow is rejected without touching the codec.
+    /// Sequences that activate or deactivate the interface, such as those in [`sequence`], are
+    /// run with [`run_power_up`](Wm8731::run_power_up),
+    /// [`run_power_down`](Wm8731::run_power_down) and [`run_standby`](Wm8731::run_standby)
+    /// instead.
+    pub fn run<T, D: DelayNs>(&mut self, steps: T, delay: &mut D) -> Result<(), I::Error>
+    where
+        T: IntoIterator<Item = Step>,
+        T::IntoIter: Clone,
+    {
+        let steps = steps.into_iter();
+        for step in steps.clone() {
+            if let Step::Write(register) = step {
+                check_state::<S, E>(register)?;
+            }
+        }
+
+        self.run_steps(steps, delay)
+    }
+
+    /// Write the left line input control register
+    pub fn left_line_in<F: FnOnce(&mut LineIn)>(&mut self, c: F) -> Result<(), I::Error> {
+        self.write(WM8731::left_line_in(c))
+    }
+
+    /// Write the right line input control register
+    pub fn right_line_in<F: FnOnce(&mut LineIn)>(&mut self, c: F) -> Result<(), I::Error> {
+        self.write(WM8731::right_line_in(c))
+    }
+
+    /// Write the left headphone out control register
+    pub fn left_headphone_out<F: FnOnce(&mut HeadphoneOut)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), I::Error> {
+        self.write(WM8731::left_headphone_out(c))
+    }
+
+    /// Write the right headphone out control register
+    pub fn right_headphone_out<F: FnOnce(&mut HeadphoneOut)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), I::Error> {
+        self.write(WM8731::right_headphone_out(c))
+    }
+
+    /// Write the left and right line input control registers in as few writes as possible
+    pub fn stereo_line_in<F: FnOnce(&mut LineIn), G: FnOnce(&mut LineIn)>(
+        &mut self,
+        left: F,
+        right: G,
+    ) -> Result<(), I::Error> {
+        for register in WM8731::stereo_line_in(left, right).iter() {
+            self.write(*register)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the left and right headphone out control registers in as few writes as possible
+    pub fn stereo_headphone_out<F: FnOnce(&mut HeadphoneOut), G: FnOnce(&mut HeadphoneOut)>(
+        &mut self,
+        left: F,
+        right: G,
+    ) -> Result<(), I::Error> {
+        for register in WM8731::stereo_headphone_out(left, right).iter() {
+            self.write(*register)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the analog audio path control register
+    pub fn analog_audio_path<F: FnOnce(&mut AnalogAudioPath)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), I::Error> {
+        self.write(WM8731::analog_audio_path(c))
+    }
+
+    /// Write the digital audio path control register
+    pub fn digital_audio_path<F: FnOnce(&mut DigitalAudioPath)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), I::Error> {
+        self.write(WM8731::digital_audio_path(c))
+    }
+
+    /// Write the power down control register
+    pub fn power_down<F: FnOnce(&mut PowerDown)>(&mut self, c: F) -> Result<(), I::Error> {
+        self.write(WM8731::power_down(c))
+    }
+}
+
+impl<I, E> Wm8731<I, Inactive>
+where
+    I: Interface<Error = Error<E>>,
+{
+    /// Write the digital audio interface format control register
+    pub fn digital_audio_interface_format<F: FnOnce(&mut DigitalAudioInterfaceFormat)>(
+        &mut self,
+        c: F,
+    ) -> Result<(), I::Error> {
+        self.write(WM8731::digital_audio_interface_format(c))
+    }
+
+    /// Write the sampling control register
+    pub fn sampling<F: FnOnce(&mut Sampling)>(&mut self, c: F) -> Result<(), I::Error> {
+        self.write(WM8731::sampling(c))
+    }
+
+    /// Activate the digital audio interface
+    ///
+    /// If the write fails, the error is returned along with the still inactive driver.
+    pub fn activate(self) -> Result<Wm8731<I, Active>, (Self, I::Error)> {
+        self.transition(WM8731::active().active())
+    }
+
+    /// Reset all registers to their defaults
+    pub fn reset(&mut self) -> Result<(), I::Error> {
+        self.write(WM8731::reset())
+    }
+
+    /// Run [`sequence::power_up`], which activates the interface
+    ///
+    /// `configuration` must not include the power down, active or reset registers; if it does,
+    /// nothing is written and [`Error::State`] is returned. If a step fails, the error is returned
+    /// along with the driver, still typed as inactive, although the interface may have been
+    /// activated.
+    ///
+    /// To bring up a [`CodecConfig`](crate::config::CodecConfig), [`reset`](Self::reset) the
+    /// codec, then pass `config.power.power_down()` and `config.power_up_configuration()`.
+    pub fn run_power_up<D: DelayNs>(
+        mut self,
+        power: PowerDown,
+        configuration: &[Register],
+        settle_ms: u32,
+        delay: &mut D,
+    ) -> Result<Wm8731<I, Active>, (Self, I::Error)> {
+        if let Some(register) = configuration
+            .iter()
+            .find(|register| matches!(register.address, POWER_DOWN | ACTIVE | RESET))
+        {
+            return Err((self, Error::State(register.address)));
+        }
+
+        match self.run_steps(sequence::power_up(power, configuration, settle_ms), delay) {
+            Ok(()) => Ok(self.into_state()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
+
+impl<I, E> Wm8731<I, Active>
+where
+    I: Interface<Error = Error<E>>,
+{
+    /// Deactivate the digital audio interface
+    ///
+    /// If the write fails, the error is returned along with the still active driver.
+    pub fn deactivate(self) -> Result<Wm8731<I, Inactive>, (Self, I::Error)> {
+        self.transition(WM8731::active().inactive())
+    }
+
+    /// Reset all registers to their defaults, which leaves the interface inactive
+    ///
+    /// If the write fails, the error is returned along with the still active driver.
+    pub fn reset(self) -> Result<Wm8731<I, Inactive>, (Self, I::Error)> {
+        self.transition(WM8731::reset())
+    }
+
+    /// Run [`sequence::power_down`], which deactivates the
+    /// interface
+    ///
+    /// If a step fails, the error is returned along with the driver, still typed as active.
+    pub fn run_power_down<D: DelayNs>(
+        self,
+        power: PowerDown,
+        path: DigitalAudioPath,
+        delay: &mut D,
+    ) -> Result<Wm8731<I, Inactive>, (Self, I::Error)> {
+        self.run_transition(sequence::power_down(power, path), delay)
+    }
+
+    /// Run [`sequence::standby`], which deactivates the interface
+    ///
+    /// If a step fails, the error is returned along with the driver, still typed as active.
+    pub fn run_standby<D: DelayNs>(
+        self,
+        power: PowerDown,
+        path: DigitalAudioPath,
+        delay: &mut D,
+    ) -> Result<Wm8731<I, Inactive>, (Self, I::Error)> {
+        self.run_transition(sequence::standby(power, path), delay)
+    }
+
+    fn run_transition<D: DelayNs>(
+        mut self,
+        steps: [Step; 4],
+        delay: &mut D,
+    ) -> Result<Wm8731<I, Inactive>, (Self, I::Error)> {
+        match self.run_steps(steps, delay) {
+            Ok(()) => Ok(self.into_state()),
+            Err(e) => Err((self, e)),
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Errors for values that the WM8731 cannot represent
+
+use core::fmt;
+
+/// A [`Register`](crate::Register) that cannot be sent to the codec
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegisterError {
+    /// The address is not one of the codec's registers
+    InvalidAddress(u8),
+    /// The value does not fit in 9 bits
+    InvalidValue(u16),
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegisterError::InvalidAddress(address) => write!(f, "R{} is not a register", address),
+            RegisterError::InvalidValue(value) => write!(f, "{:#x} does not fit in 9 bits", value),
+        }
+    }
+}
+
+/// A gain that the codec cannot be set to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GainError {
+    /// The gain is below the minimum or above the maximum supported
+    OutOfRange,
+    /// The gain falls between two of the steps supported
+    OffGrid,
+}
+
+impl fmt::Display for GainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GainError::OutOfRange => "gain is out of range",
+            GainError::OffGrid => "gain is between two steps",
+        })
+    }
+}
+
+/// Sample rates that cannot be derived from the master clock
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RateError {
+    /// The MCLK frequency, in Hz, is not one the codec supports
+    UnsupportedMclk(u32),
+    /// The ADC/DAC rate pair cannot be derived from this MCLK; these are the ones that can
+    Unachievable(crate::rate_solver::Achievable),
+}
+
+impl fmt::Display for RateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateError::UnsupportedMclk(hz) => write!(f, "{}Hz MCLK is not supported", hz),
+            RateError::Unachievable(achievable) => {
+                f.write_str("sample rates are not achievable with this MCLK; ADC/DAC can be")?;
+                for (i, (adc, dac)) in achievable.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(f, "{}{}/{}", separator, adc, dac)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A [`CodecConfig`](crate::config::CodecConfig) that cannot be rendered to registers
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// A line input gain or headphone volume is not supported
+    Gain(GainError),
+    /// The sample rates cannot be derived from the MCLK frequency
+    Rate(RateError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Gain(e) => write!(f, "{}", e),
+            ConfigError::Rate(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<GainError> for ConfigError {
+    fn from(e: GainError) -> Self {
+        ConfigError::Gain(e)
+    }
+}
+
+impl From<RateError> for ConfigError {
+    fn from(e: RateError) -> Self {
+        ConfigError::Rate(e)
+    }
+}
+
+/// A register value that does not correspond to valid settings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The register could not have been written to the codec
+    Register(RegisterError),
+    /// Bits the datasheet reserves are set
+    ReservedBits { address: u8, value: u16 },
+    /// The sample rate code is not one of those in the datasheet
+    ReservedSampleRate(u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Register(e) => write!(f, "{}", e),
+            DecodeError::ReservedBits { address, value } => {
+                write!(f, "R{} has reserved bits set in {:#05x}", address, value)
+            }
+            DecodeError::ReservedSampleRate(code) => {
+                write!(f, "sample rate code {:#06b} is reserved", code)
+            }
+        }
+    }
+}
+
+impl From<RegisterError> for DecodeError {
+    fn from(e: RegisterError) -> Self {
+        DecodeError::Register(e)
+    }
+}
+
+/// Master mode clocks that cannot be worked out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimingError {
+    /// The codec is in slave mode, so BCLK and the frame clocks come from elsewhere
+    Slave,
+    /// The sample rate code is not one of those in the datasheet
+    ReservedSampleRate(u8),
+}
+
+impl fmt::Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimingError::Slave => f.write_str("the codec is in slave mode"),
+            TimingError::ReservedSampleRate(code) => {
+                write!(f, "sample rate code {:#06b} is reserved", code)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RegisterError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for GainError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RateError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Gain(e) => Some(e),
+            ConfigError::Rate(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TimingError {}
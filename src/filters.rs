@@ -0,0 +1,192 @@
+//! Software model of the codec's digital filters
+//!
+//! For previewing how the digital audio path settings change a signal, such as in regression
+//! tests of a DSP chain. [`AdcFilter`] models the ADC high pass filter and its DC offset store,
+//! and [`DacFilter`] the DAC de-emphasis filter and mute. Each filter handles one channel of
+//! full-scale `f32` samples. Requires the `std` feature.
+//!
+//! These are models of the documented behaviour, not bit-exact copies of the codec's filters.
+//!
+//! ```
+//! # use wm8731::digital_audio_path::DigitalAudioPath;
+//! # use wm8731::filters::AdcFilter;
+//! let path = DigitalAudioPath::new();
+//! let mut filter = AdcFilter::new(&path);
+//!
+//! // A DC input settles to zero
+//! let mut samples = [0.5; 48_000];
+//! filter.process(&mut samples);
+//! assert!(samples[47_999].abs() < 0.001);
+//! ```
+
+use core::f64::consts::PI;
+
+use crate::config::Deemphasis;
+use crate::decode;
+use crate::digital_audio_path::DigitalAudioPath;
+
+/// High pass filter -3dB point at a 48kHz sample rate; it scales with the sample rate
+const HPF_CORNER_HZ: f64 = 3.7;
+
+/// De-emphasis time constants, in seconds
+const DEEMPHASIS_T1: f64 = 50e-6;
+const DEEMPHASIS_T2: f64 = 15e-6;
+
+/// ADC high pass filter
+///
+/// The filter removes DC by tracking and subtracting the input's offset. When the filter is
+/// disabled, the last offset is either kept and still subtracted or cleared, as set by `hpor`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AdcFilter {
+    enabled: bool,
+    store_dc_offset: bool,
+    offset: f64,
+    /// Fraction of the difference between the input and the offset tracked each sample
+    rate: f64,
+}
+
+impl AdcFilter {
+    /// A filter with no stored offset, set up as in `path`
+    pub fn new(path: &DigitalAudioPath) -> Self {
+        let mut filter = AdcFilter {
+            enabled: true,
+            store_dc_offset: false,
+            offset: 0.0,
+            rate: 1.0 - (-2.0 * PI * HPF_CORNER_HZ / 48_000.0).exp(),
+        };
+        filter.configure(path);
+
+        filter
+    }
+
+    /// Apply new settings, keeping the stored offset where the codec would
+    pub fn configure(&mut self, path: &DigitalAudioPath) {
+        let dap = decode::digital_audio_path(path.data);
+        self.enabled = dap.adc_hpf;
+        self.store_dc_offset = dap.store_dc_offset;
+        if !self.enabled && !self.store_dc_offset {
+            self.offset = 0.0;
+        }
+    }
+
+    /// DC offset currently subtracted from the input
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    /// Filter samples in place
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            let x = f64::from(*sample);
+            if self.enabled {
+                self.offset += self.rate * (x - self.offset);
+            }
+            *sample = (x - self.offset) as f32;
+        }
+    }
+}
+
+/// First order de-emphasis filter coefficients
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Coefficients {
+    b0: f64,
+    b1: f64,
+    a1: f64,
+}
+
+impl Coefficients {
+    /// The 50/15µs de-emphasis curve for a sample rate, by bilinear transform with both corner
+    /// frequencies prewarped
+    fn deemphasis(sample_rate: f64) -> Self {
+        let k = 2.0 * sample_rate;
+        let prewarp = |t: f64| 1.0 / (k * (1.0 / (k * t)).tan());
+        let (t1, t2) = (prewarp(DEEMPHASIS_T1), prewarp(DEEMPHASIS_T2));
+        let norm = 1.0 + k * t1;
+
+        Coefficients {
+            b0: (1.0 + k * t2) / norm,
+            b1: (1.0 - k * t2) / norm,
+            a1: (1.0 - k * t1) / norm,
+        }
+    }
+}
+
+/// DAC de-emphasis filter and mute
+///
+/// The codec's DAC soft mute ramps the signal down gradually, but the ramp is not documented, so
+/// this model mutes immediately: a hard mute from the first sample after the mute is configured.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DacFilter {
+    coefficients: Option<Coefficients>,
+    sample_rate: f64,
+    mute: bool,
+    x1: f64,
+    y1: f64,
+}
+
+impl DacFilter {
+    /// A filter at rest, set up as in `path`
+    pub fn new(path: &DigitalAudioPath) -> Self {
+        let mut filter = DacFilter {
+            coefficients: None,
+            sample_rate: 48_000.0,
+            mute: false,
+            x1: 0.0,
+            y1: 0.0,
+        };
+        filter.configure(path);
+
+        filter
+    }
+
+    /// Apply new settings
+    ///
+    /// The filter is designed for the de-emphasis frequency, so the DAC should be running at
+    /// that sample rate.
+    pub fn configure(&mut self, path: &DigitalAudioPath) {
+        let dap = decode::digital_audio_path(path.data);
+        self.mute = dap.dac_mute;
+        let sample_rate = match dap.deemphasis {
+            Deemphasis::Disabled => None,
+            Deemphasis::Khz32 => Some(32_000.0),
+            Deemphasis::Khz441 => Some(44_100.0),
+            Deemphasis::Khz48 => Some(48_000.0),
+        };
+        self.coefficients = sample_rate.map(Coefficients::deemphasis);
+        if let Some(sample_rate) = sample_rate {
+            self.sample_rate = sample_rate;
+        }
+    }
+
+    /// Gain in dB at `frequency_hz`
+    pub fn response_db(&self, frequency_hz: f64) -> f64 {
+        let c = match self.coefficients {
+            Some(c) => c,
+            None => return 0.0,
+        };
+        let w = 2.0 * PI * frequency_hz / self.sample_rate;
+        let (sin, cos) = (w.sin(), w.cos());
+        // H(e^jw) = (b0 + b1 e^-jw) / (1 + a1 e^-jw)
+        let numerator = (c.b0 + c.b1 * cos).powi(2) + (c.b1 * sin).powi(2);
+        let denominator = (1.0 + c.a1 * cos).powi(2) + (c.a1 * sin).powi(2);
+
+        10.0 * (numerator / denominator).log10()
+    }
+
+    /// Filter samples in place
+    ///
+    /// While the DAC is muted every sample is zero, without the ramp of the codec's soft mute.
+    /// The de-emphasis filter keeps running, so unmuting carries on from its current state.
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            let x = f64::from(*sample);
+            let y = match self.coefficients {
+                Some(c) => c.b0 * x + c.b1 * self.x1 - c.a1 * self.y1,
+                None => x,
+            };
+            self.x1 = x;
+            self.y1 = y;
+            *sample = if self.mute { 0.0 } else { y as f32 };
+        }
+    }
+}
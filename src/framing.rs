@@ -0,0 +1,176 @@
+//! Pack and unpack audio samples in the layout set by the digital audio interface format
+//!
+//! Each stereo frame is carried in two 32-bit words, as if BCLK ran at 64fs: the bits of the
+//! first word are clocked out MSB first from the start of the left channel (the LRCLK edge, or
+//! the frame sync pulse in DSP mode), followed by the bits of the second word. Where each sample
+//! sits in those 64 bits depends on the format:
+//!
+//! - Left justified: the left sample starts at the first bit, the right sample at the 33rd
+//! - I²S: as left justified, delayed by one bit clock
+//! - Right justified: each sample ends at the last bit of its word
+//! - DSP: the right sample directly follows the left one, delayed by one bit clock when
+//!   `left_right_phase` is set (MSB on the 2nd BCLK rising edge)
+//!
+//! When a delayed sample runs past the end of a frame, as the right channel of a 32-bit I²S
+//! frame does, its last bit is carried in the first bit of the next frame; after the last frame
+//! of a buffer it is dropped.
+//!
+//! Samples are interleaved left, right and full-scale: an `i16` or `i32` sample fills its whole
+//! range whatever the word length, and the most significant bits are the ones transferred.
+//!
+//! ```
+//! # use wm8731::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+//! # use wm8731::framing::Framing;
+//! let mut format = DigitalAudioInterfaceFormat::new();
+//! format.format().left_justified();
+//! format.bit_length().bits_16();
+//!
+//! let framing = Framing::new(&format);
+//! let mut words = [0; 4];
+//! assert_eq!(framing.pack_i16(&[0x1234, -1, 0, 1], &mut words), 2);
+//! assert_eq!(words, [0x1234_0000, 0xffff_0000, 0, 0x0001_0000]);
+//! ```
+
+use crate::config::{AudioFormat, FormatConfig};
+use crate::decode;
+use crate::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+
+/// Sample layout for a digital audio interface format
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Framing {
+    format: AudioFormat,
+    bits: u32,
+    /// DSP mode with the MSB on the 2nd BCLK rising edge
+    delayed_dsp: bool,
+    /// Right channel DAC data on the left
+    swap: bool,
+}
+
+impl Framing {
+    /// Layout for the settings in a digital audio interface format register
+    pub fn new(format: &DigitalAudioInterfaceFormat) -> Self {
+        Self::from(decode::digital_audio_interface_format(format.data))
+    }
+
+    /// Bits per sample
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// Offsets of the left and right samples from the start of the frame, in bits
+    fn offsets(&self) -> (u32, u32) {
+        match self.format {
+            AudioFormat::LeftJustified => (0, 32),
+            AudioFormat::I2s => (1, 33),
+            AudioFormat::RightJustified => (32 - self.bits, 64 - self.bits),
+            AudioFormat::Dsp if self.delayed_dsp => (1, 1 + self.bits),
+            AudioFormat::Dsp => (0, self.bits),
+        }
+    }
+
+    /// Pack samples, given as left-aligned 32-bit values, into words
+    fn pack<T: Copy>(&self, samples: &[T], words: &mut [u32], full: fn(T) -> u32) -> usize {
+        let (left_offset, right_offset) = self.offsets();
+        let mut carry = 0;
+        let mut frames = 0;
+
+        for (frame, out) in samples.chunks_exact(2).zip(words.chunks_exact_mut(2)) {
+            let (left, right) = if self.swap {
+                (frame[1], frame[0])
+            } else {
+                (frame[0], frame[1])
+            };
+
+            let mut bits = carry;
+            carry = 0;
+            for &(sample, offset) in [(left, left_offset), (right, right_offset)].iter() {
+                let value = u64::from(full(sample) >> (32 - self.bits));
+                let end = offset + self.bits;
+                if end <= 64 {
+                    bits |= value << (64 - end);
+                } else {
+                    let spill = end - 64;
+                    bits |= value >> spill;
+                    carry = value << (64 - spill);
+                }
+            }
+
+            out[0] = (bits >> 32) as u32;
+            out[1] = bits as u32;
+            frames += 1;
+        }
+
+        frames
+    }
+
+    /// Unpack words into samples, given as left-aligned 32-bit values
+    fn unpack<T>(&self, words: &[u32], samples: &mut [T], sample: fn(u32) -> T) -> usize {
+        let (left_offset, right_offset) = self.offsets();
+        let mask = (1u64 << self.bits) - 1;
+        let frame_bits = |frame: &[u32]| u64::from(frame[0]) << 32 | u64::from(frame[1]);
+        let mut frames = 0;
+
+        let mut chunks = words.chunks_exact(2).peekable();
+        for out in samples.chunks_exact_mut(2) {
+            let bits = match chunks.next() {
+                Some(frame) => frame_bits(frame),
+                None => break,
+            };
+            let next = chunks.peek().map_or(0, |frame| frame_bits(frame));
+
+            for (out, &offset) in out.iter_mut().zip([left_offset, right_offset].iter()) {
+                let end = offset + self.bits;
+                let value = if end <= 64 {
+                    bits >> (64 - end)
+                } else {
+                    let spill = end - 64;
+                    bits << spill | next >> (64 - spill)
+                };
+                *out = sample(((value & mask) as u32) << (32 - self.bits));
+            }
+            frames += 1;
+        }
+
+        frames
+    }
+
+    /// Pack samples for the DAC
+    ///
+    /// `samples` holds interleaved left and right samples, and each frame fills two `words`.
+    /// The channels are swapped if `left_right_dac_clock_swap` is set. Returns the number of
+    /// frames packed, which is limited by the shorter of the two slices.
+    pub fn pack_i32(&self, samples: &[i32], words: &mut [u32]) -> usize {
+        self.pack(samples, words, |sample| sample as u32)
+    }
+
+    /// Pack samples for the DAC; see [`pack_i32`](Self::pack_i32)
+    pub fn pack_i16(&self, samples: &[i16], words: &mut [u32]) -> usize {
+        self.pack(samples, words, |sample| (sample as u32) << 16)
+    }
+
+    /// Unpack samples from the ADC
+    ///
+    /// Each frame takes two `words` and fills a left and a right sample in `samples`. Channel
+    /// swapping only applies to the DAC, so it does not affect unpacking. Returns the number of
+    /// frames unpacked, which is limited by the shorter of the two slices.
+    pub fn unpack_i32(&self, words: &[u32], samples: &mut [i32]) -> usize {
+        self.unpack(words, samples, |value| value as i32)
+    }
+
+    /// Unpack samples from the ADC, keeping the 16 most significant bits; see
+    /// [`unpack_i32`](Self::unpack_i32)
+    pub fn unpack_i16(&self, words: &[u32], samples: &mut [i16]) -> usize {
+        self.unpack(words, samples, |value| (value >> 16) as i16)
+    }
+}
+
+impl From<FormatConfig> for Framing {
+    fn from(config: FormatConfig) -> Self {
+        Framing {
+            format: config.format,
+            bits: config.word_length.bits(),
+            delayed_dsp: config.left_right_phase,
+            swap: config.left_right_swap,
+        }
+    }
+}
@@ -1,12 +1,70 @@
 //! Configuration for headphone outputs
 
-use crate::EnableDisable;
+use core::fmt;
 
-#[derive(Debug, Copy, Clone)]
+use crate::bitmask::BitMask;
+use crate::decode;
+use crate::display::{Enabled, HalfDb, Plain, MUTED};
+use crate::{EnableDisable, GainError};
+
+#[derive(Copy, Clone)]
 pub struct HeadphoneOut {
     pub(crate) data: u16,
 }
 
+pub struct Volume<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> Volume<'a> {
+    /// Lowest gain the headphone output supports, in dB
+    #[allow(non_upper_case_globals)]
+    pub const MIN_dB: i16 = -73;
+
+    /// Highest gain the headphone output supports, in dB
+    #[allow(non_upper_case_globals)]
+    pub const MAX_dB: i16 = 6;
+
+    /// Headphone output volume in 1dB steps.
+    ///
+    /// The headphone output has 1dB steps, so every whole dB gain in range is exact and this is
+    /// the counterpart of
+    /// [`line_in::Volume::try_nearest_dB`](crate::line_in::Volume::try_nearest_dB).
+    ///
+    /// Returns [`GainError::OutOfRange`] and leaves the volume unchanged if `dB_gain` is below
+    /// -73dB or above 6dB.
+    #[allow(non_snake_case)]
+    pub fn dB(&mut self, dB_gain: i16) -> Result<(), GainError> {
+        if !(Self::MIN_dB..=Self::MAX_dB).contains(&dB_gain) {
+            return Err(GainError::OutOfRange);
+        }
+
+        // 0b111_1001 is 0dB
+        self.bitmask
+            .apply(self.index, 7, (dB_gain + 0b111_1001) as u16);
+
+        Ok(())
+    }
+
+    /// Headphone output volume in 1dB steps, clamped to the supported range
+    ///
+    /// Like [`dB`](Self::dB), but gains below -73dB or above 6dB are clamped to the minimum or
+    /// maximum instead of being rejected, so this never fails. This mirrors
+    /// [`line_in::Volume::saturating_nearest_dB`](crate::line_in::Volume::saturating_nearest_dB).
+    #[allow(non_snake_case)]
+    pub fn saturating_nearest_dB(&mut self, dB_gain: i16) {
+        let dB_gain = dB_gain.clamp(Self::MIN_dB, Self::MAX_dB);
+        self.bitmask
+            .apply(self.index, 7, (dB_gain + 0b111_1001) as u16);
+    }
+
+    /// Mute the headphone output
+    pub fn mute(&mut self) {
+        self.bitmask.apply(self.index, 7, 0);
+    }
+}
+
 impl HeadphoneOut {
     pub fn new() -> Self {
         HeadphoneOut {
@@ -21,18 +79,60 @@ impl HeadphoneOut {
     /// Max: +6dB
     ///
     /// Step: 1dB
-    pub fn volume(&mut self, volume: u16) {
-        self.data |= 0b0_0000_0000
+    pub fn volume(&mut self) -> Volume<'_> {
+        Volume {
+            index: 0,
+            bitmask: BitMask::new(&mut self.data),
+        }
     }
 
     /// Zero cross detect
-    pub fn zero_cross_detect(&mut self) -> EnableDisable {
+    pub fn zero_cross_detect(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(7, &mut self.data)
     }
 
     /// Left to right channel headphone volume, mute, and zero cross data load
     /// When enabled, left and right channels will have the same values
-    pub fn both(&mut self) -> EnableDisable {
+    pub fn both(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(8, &mut self.data)
     }
 }
+
+impl fmt::Debug for HeadphoneOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ho = decode::headphone_out(self.data);
+
+        let mut s = f.debug_struct("HeadphoneOut");
+        match ho.volume_db {
+            Some(db) => s.field("volume", &HalfDb(db * 2)),
+            None => s.field("volume", &Plain(MUTED)),
+        };
+        s.field("zero_cross_detect", &Enabled(ho.zero_cross_detect))
+            .field("both", &Enabled(ho.both))
+            .finish()
+    }
+}
+
+impl fmt::Display for HeadphoneOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for HeadphoneOut {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let ho = decode::headphone_out(self.data);
+
+        match ho.volume_db {
+            Some(db) => defmt::write!(f, "HeadphoneOut {{ volume: {}", HalfDb(db * 2)),
+            None => defmt::write!(f, "HeadphoneOut {{ volume: {=str}", MUTED),
+        }
+        defmt::write!(
+            f,
+            ", zero_cross_detect: {}, both: {} }}",
+            Enabled(ho.zero_cross_detect),
+            Enabled(ho.both)
+        )
+    }
+}
@@ -0,0 +1,76 @@
+//! Transports for the control interface
+//!
+//! The WM8731 accepts control writes over 2-wire I²C (MODE pin low) or 3-wire SPI (MODE pin
+//! high). Both carry the same 16-bit word: the 7-bit register address followed by the 9-bit
+//! value, MSB first.
+
+use embedded_hal::i2c::I2c;
+use embedded_hal::spi::SpiDevice;
+
+use crate::driver::Error;
+use crate::Register;
+
+/// A bus that can write registers to the codec
+pub trait Interface {
+    type Error;
+
+    /// Write a register to the codec
+    fn write(&mut self, register: Register) -> Result<(), Self::Error>;
+}
+
+/// 2-wire I²C control interface
+pub struct I2cInterface<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C: I2c> I2cInterface<I2C> {
+    /// Create an interface for a codec at the given 7-bit I²C address
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        I2cInterface { i2c, address }
+    }
+
+    /// Give back the I²C bus
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C: I2c> Interface for I2cInterface<I2C> {
+    type Error = Error<I2C::Error>;
+
+    fn write(&mut self, register: Register) -> Result<(), Self::Error> {
+        self.i2c
+            .write(self.address, &register.to_bytes()?)
+            .map_err(Error::from_i2c)
+    }
+}
+
+/// 3-wire SPI control interface
+///
+/// The `SpiDevice` must drive the codec's CSB pin as its chip select: the codec latches the
+/// 16-bit word on the rising edge of CSB, which happens when the device releases chip select at
+/// the end of each write. SDIN is sampled on the rising edge of SCLK, so use SPI mode 0 or 3.
+pub struct SpiInterface<SPI> {
+    spi: SPI,
+}
+
+impl<SPI: SpiDevice> SpiInterface<SPI> {
+    /// Create an interface for a codec with its CSB pin managed by `spi`
+    pub fn new(spi: SPI) -> Self {
+        SpiInterface { spi }
+    }
+
+    /// Give back the SPI device
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+}
+
+impl<SPI: SpiDevice> Interface for SpiInterface<SPI> {
+    type Error = Error<SPI::Error>;
+
+    fn write(&mut self, register: Register) -> Result<(), Self::Error> {
+        self.spi.write(&register.to_bytes()?).map_err(Error::Bus)
+    }
+}
@@ -15,11 +15,34 @@
 //!     c.dac().power_off();
 //! });
 //! ```
+//!
+//! To write the registers to the codec over I²C or SPI, use the [`driver::Wm8731`] driver, or
+//! `async_driver::AsyncWm8731` with the `async` feature enabled.
+//!
+//! With the `defmt` feature enabled, registers, builders and errors implement `defmt::Format`,
+//! logging each field by meaning rather than as raw bits.
+//!
+//! With the `serde` feature enabled, [`Register`] and the typed settings in [`config`] and
+//! [`decode`] can be serialized, so the same file can configure a host tool and the firmware.
+//!
+//! With the `std` feature enabled, `filters` models the ADC high pass and DAC de-emphasis
+//! filters for previewing their effect on a signal.
+//!
+//! With the `linux` feature enabled, `linux::LinuxI2c` drives the codec through a Linux i2c-dev
+//! adapter, such as `/dev/i2c-1` on a single-board computer.
+//!
+//! The `cli` feature builds a `wm8731` command line tool that generates register writes from
+//! flags or a TOML configuration, decodes register dumps, and prints init sequences as C arrays,
+//! Rust arrays or `i2cset` commands. Its parsing and output live in `cli`.
 
 #![no_std]
 #![allow(clippy::new_without_default)]
 
+#[cfg(feature = "std")]
+extern crate std;
+
 mod bitmask;
+mod display;
 
 pub mod enable_disable;
 pub use enable_disable::EnableDisable;
@@ -51,11 +74,178 @@ use active::Active;
 pub mod sampling_rate;
 use sampling_rate::SamplingRate;
 
+pub mod error;
+pub use error::{ConfigError, DecodeError, GainError, RateError, RegisterError, TimingError};
+
+pub mod rate_solver;
+
+pub mod shadow;
+
+pub mod sequence;
+
+pub mod stereo;
+use stereo::StereoWrite;
+
+pub mod config;
+
+pub mod decode;
+
+pub mod validate;
+
+pub mod framing;
+
+pub mod timing;
+
+#[cfg(feature = "std")]
+pub mod filters;
+
+pub mod interface;
+
+#[cfg(feature = "linux")]
+pub mod linux;
+
+#[cfg(feature = "cli")]
+pub mod cli;
+
+pub mod simulator;
+
+pub mod driver;
+
+#[cfg(feature = "async")]
+pub mod async_driver;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Register {
     pub address: u8,
     pub value: u16,
 }
 
+/// Deserialized like a `Register`, but checked for a valid address and value
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Register {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "Register")]
+        struct Fields {
+            address: u8,
+            value: u16,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let register = Register {
+            address: fields.address,
+            value: fields.value,
+        };
+        match register.to_u16() {
+            Ok(_) => Ok(register),
+            Err(RegisterError::InvalidAddress(address)) => Err(D::Error::custom(format_args!(
+                "{} is not a register address",
+                address
+            ))),
+            Err(RegisterError::InvalidValue(value)) => Err(D::Error::custom(format_args!(
+                "{:#x} does not fit in a 9-bit register",
+                value
+            ))),
+        }
+    }
+}
+
+impl Register {
+    /// Addresses of every register on the WM8731
+    pub const ADDRESSES: [u8; 11] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 15];
+
+    /// Largest value that fits in a register
+    pub const MAX_VALUE: u16 = 0x1ff;
+
+    /// Encode as the 16-bit control word: the 7-bit address in bits 9-15, the 9-bit value in
+    /// bits 0-8
+    pub fn to_u16(&self) -> Result<u16, RegisterError> {
+        if !Self::ADDRESSES.contains(&self.address) {
+            return Err(RegisterError::InvalidAddress(self.address));
+        }
+        if self.value > Self::MAX_VALUE {
+            return Err(RegisterError::InvalidValue(self.value));
+        }
+
+        Ok(((self.address as u16) << 9) | self.value)
+    }
+
+    /// Encode as the two bytes sent over the control interface, most significant byte first
+    pub fn to_bytes(&self) -> Result<[u8; 2], RegisterError> {
+        self.to_u16().map(u16::to_be_bytes)
+    }
+
+    /// Decode a 16-bit control word
+    pub fn from_u16(word: u16) -> Result<Self, RegisterError> {
+        let register = Register {
+            address: (word >> 9) as u8,
+            value: word & Self::MAX_VALUE,
+        };
+        register.to_u16()?;
+
+        Ok(register)
+    }
+
+    /// Decode the two bytes sent over the control interface, most significant byte first
+    pub fn from_bytes(bytes: [u8; 2]) -> Result<Self, RegisterError> {
+        Self::from_u16(u16::from_be_bytes(bytes))
+    }
+}
+
+impl core::fmt::Display for Register {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let data = self.value;
+        match self.address {
+            0 => write!(f, "R0 left line in: {}", LineIn { data }),
+            1 => write!(f, "R1 right line in: {}", LineIn { data }),
+            2 => write!(f, "R2 left headphone out: {}", HeadphoneOut { data }),
+            3 => write!(f, "R3 right headphone out: {}", HeadphoneOut { data }),
+            4 => write!(f, "R4 analog audio path: {}", AnalogAudioPath { data }),
+            5 => write!(f, "R5 digital audio path: {}", DigitalAudioPath { data }),
+            6 => write!(f, "R6 power down: {}", PowerDown { data }),
+            7 => write!(
+                f,
+                "R7 digital audio interface format: {}",
+                DigitalAudioInterfaceFormat { data }
+            ),
+            8 => write!(f, "R8 sampling: {}", Sampling { data }),
+            9 if data & 1 != 0 => write!(f, "R9 active"),
+            9 => write!(f, "R9 inactive"),
+            15 => write!(f, "R15 reset"),
+            address => write!(f, "R{} (unknown): {:#05x}", address, data),
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Register {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let data = self.value;
+        match self.address {
+            0 => defmt::write!(f, "R0 left line in: {}", LineIn { data }),
+            1 => defmt::write!(f, "R1 right line in: {}", LineIn { data }),
+            2 => defmt::write!(f, "R2 left headphone out: {}", HeadphoneOut { data }),
+            3 => defmt::write!(f, "R3 right headphone out: {}", HeadphoneOut { data }),
+            4 => defmt::write!(f, "R4 analog audio path: {}", AnalogAudioPath { data }),
+            5 => defmt::write!(f, "R5 digital audio path: {}", DigitalAudioPath { data }),
+            6 => defmt::write!(f, "R6 power down: {}", PowerDown { data }),
+            7 => defmt::write!(
+                f,
+                "R7 digital audio interface format: {}",
+                DigitalAudioInterfaceFormat { data }
+            ),
+            8 => defmt::write!(f, "R8 sampling: {}", Sampling { data }),
+            9 if data & 1 != 0 => defmt::write!(f, "R9 active"),
+            9 => defmt::write!(f, "R9 inactive"),
+            15 => defmt::write!(f, "R15 reset"),
+            address => defmt::write!(f, "R{=u8} (unknown): {=u16:#05x}", address, data),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests;
 
@@ -107,6 +297,36 @@ impl WM8731 {
         }
     }
 
+    /// Left and right line in control registers, in as few writes as possible
+    ///
+    /// See [`stereo`] for how the "both" bit is used.
+    pub fn stereo_line_in<F: FnOnce(&mut LineIn), G: FnOnce(&mut LineIn)>(
+        left: F,
+        right: G,
+    ) -> StereoWrite {
+        let mut lli = LineIn::new();
+        left(&mut lli);
+        let mut rli = LineIn::new();
+        right(&mut rli);
+
+        StereoWrite::new(0, lli.data, rli.data)
+    }
+
+    /// Left and right headphone out control registers, in as few writes as possible
+    ///
+    /// See [`stereo`] for how the "both" bit is used.
+    pub fn stereo_headphone_out<F: FnOnce(&mut HeadphoneOut), G: FnOnce(&mut HeadphoneOut)>(
+        left: F,
+        right: G,
+    ) -> StereoWrite {
+        let mut lho = HeadphoneOut::new();
+        left(&mut lho);
+        let mut rho = HeadphoneOut::new();
+        right(&mut rho);
+
+        StereoWrite::new(2, lho.data, rho.data)
+    }
+
     /// Analog audio path control register
     pub fn analog_audio_path<F: FnOnce(&mut AnalogAudioPath)>(c: F) -> Register {
         let mut aap = AnalogAudioPath::new();
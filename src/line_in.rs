@@ -1,9 +1,13 @@
 //! Configuration for line inputs
 
+use core::fmt;
+
 use crate::bitmask::BitMask;
-use crate::EnableDisable;
+use crate::decode;
+use crate::display::{Enabled, HalfDb};
+use crate::{EnableDisable, GainError};
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Copy, Clone)]
 pub struct LineIn {
     pub(crate) data: u16,
 }
@@ -14,11 +18,44 @@ pub struct Volume<'a> {
 }
 
 impl<'a> Volume<'a> {
+    /// Lowest gain the line input supports, in half-dB steps
+    #[allow(non_upper_case_globals)]
+    pub const MIN_half_dBs: i16 = -69;
+
+    /// Highest gain the line input supports, in half-dB steps
+    #[allow(non_upper_case_globals)]
+    pub const MAX_half_dBs: i16 = 24;
+
     /// Line input volume in half-dB steps.
     ///
     /// The `half_dBs` parameter value must be twice the desired dB gain; for example:
-    ///    * for a 1.5dB gain, call `half_dB_steps(3)`
-    ///    * for 9dB attenuation, call `half_dB_steps(-18)`
+    ///    * for a 1.5dB gain, call `try_half_dB_steps(3)`
+    ///    * for 9dB attenuation, call `try_half_dB_steps(-18)`
+    ///
+    /// Returns [`GainError::OutOfRange`] if `half_dBs` is below -34.5dB or above 12dB, or
+    /// [`GainError::OffGrid`] if it is not an exact multiple of 1.5dB. The volume is left
+    /// unchanged on error.
+    #[allow(non_snake_case)]
+    pub fn try_half_dB_steps(&mut self, half_dBs: i16) -> Result<(), GainError> {
+        // The WM8731 supports -34.5dB up to 12dB.  Make sure the input is in range:
+        if !(Self::MIN_half_dBs..=Self::MAX_half_dBs).contains(&half_dBs) {
+            return Err(GainError::OutOfRange);
+        }
+
+        let offset = half_dBs - Self::MIN_half_dBs;
+        // and make sure the input lines up on the 1.5dB steps:
+        if offset % 3 != 0 {
+            return Err(GainError::OffGrid);
+        }
+
+        self.bitmask.apply(self.index, 5, (offset / 3) as u16);
+
+        Ok(())
+    }
+
+    /// Line input volume in half-dB steps.
+    ///
+    /// See [`try_half_dB_steps`](Self::try_half_dB_steps).
     ///
     /// # Panics
     ///
@@ -26,14 +63,24 @@ impl<'a> Volume<'a> {
     /// `half_dBs` parameter does not correspond to an exact multiple of 1.5dB.
     #[allow(non_snake_case)]
     fn half_dB_steps(&mut self, half_dBs: i16) {
-        // The WM8731 supports -34.5dB up to 12dB.  Make sure the input is in range:
-        assert!((-69..=24).contains(&half_dBs));
-
-        let offset = half_dBs + 69;
-        // and make sure the input lines up on the 1.5dB steps:
-        assert_eq!(offset % 3, 0);
+        if let Err(e) = self.try_half_dB_steps(half_dBs) {
+            panic!("{} half dB steps: {:?}", half_dBs, e);
+        }
+    }
 
-        self.bitmask.apply(self.index, 5, (offset / 3) as u16);
+    /// Round a gain in dB to the nearest multiple of 1.5dB, in half-dB steps
+    ///
+    /// Gains too large to represent in half-dB steps saturate, which keeps them out of range.
+    #[allow(non_snake_case)]
+    fn round_dB(dB_gain: i16) -> i16 {
+        let half_dBs = i32::from(dB_gain) * 2;
+        let rounded = match half_dBs.rem_euclid(3) {
+            0 => half_dBs,
+            1 => half_dBs - 1,
+            2 => half_dBs + 1,
+            x => unreachable!("{} cannot possibly be the result of .rem_euclid(3)", x),
+        };
+        rounded.clamp(i16::MIN.into(), i16::MAX.into()) as i16
     }
 
     /// Set line input volume to nearest representable value
@@ -44,20 +91,35 @@ impl<'a> Volume<'a> {
     ///    * 1dB will get rounded up to 1.5dB
     ///    * 2dB will get rounded down to 1.5dB
     ///
+    /// Returns [`GainError::OutOfRange`] and leaves the volume unchanged if `dB_gain` is out of
+    /// range (below -35 or above 12, covering the hardware's capability of -34.5dB to 12dB).
+    #[allow(non_snake_case)]
+    pub fn try_nearest_dB(&mut self, dB_gain: i16) -> Result<(), GainError> {
+        self.try_half_dB_steps(Self::round_dB(dB_gain))
+    }
+
+    /// Set line input volume to nearest representable value
+    ///
+    /// See [`try_nearest_dB`](Self::try_nearest_dB).
+    ///
     /// # Panics
     ///
     /// Panics if `dB_gain` is out of range (below -35 or above 12, covering the hardware's
     /// capability of -34.5dB to 12dB).
     #[allow(non_snake_case)]
     pub fn nearest_dB(&mut self, dB_gain: i16) {
-        let half_dBs = dB_gain * 2;
-        let rounded_to_multiple_of_three = match half_dBs.rem_euclid(3) {
-            0 => half_dBs,
-            1 => half_dBs - 1,
-            2 => half_dBs + 1,
-            x => panic!("{} cannot possibly be the result of .rem_euclid(3)", x),
-        };
-        self.half_dB_steps(rounded_to_multiple_of_three);
+        self.half_dB_steps(Self::round_dB(dB_gain));
+    }
+
+    /// Set line input volume to nearest representable value, clamped to the supported range
+    ///
+    /// Like [`try_nearest_dB`](Self::try_nearest_dB), but gains below -34.5dB or above 12dB are
+    /// clamped to the minimum or maximum instead of being rejected, so this never fails.
+    #[allow(non_snake_case)]
+    pub fn saturating_nearest_dB(&mut self, dB_gain: i16) {
+        let half_dBs = Self::round_dB(dB_gain).clamp(Self::MIN_half_dBs, Self::MAX_half_dBs);
+        self.bitmask
+            .apply(self.index, 5, ((half_dBs - Self::MIN_half_dBs) / 3) as u16);
     }
 }
 
@@ -69,7 +131,7 @@ impl LineIn {
     }
 
     /// Line input volume
-    pub fn volume(&mut self) -> Volume {
+    pub fn volume(&mut self) -> Volume<'_> {
         Volume {
             index: 0,
             bitmask: BitMask::new(&mut self.data),
@@ -77,13 +139,46 @@ impl LineIn {
     }
 
     /// Line input mute to ADC
-    pub fn mute(&mut self) -> EnableDisable {
+    pub fn mute(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(7, &mut self.data)
     }
 
     /// Left to right channel line input volume and mute data load
     /// When enabled, left and right channels will have same volume and mute values
-    pub fn both(&mut self) -> EnableDisable {
+    pub fn both(&mut self) -> EnableDisable<'_> {
         EnableDisable::new(8, &mut self.data)
     }
 }
+
+impl fmt::Debug for LineIn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let li = decode::line_in(self.data);
+
+        f.debug_struct("LineIn")
+            .field("volume", &HalfDb(li.gain_half_db))
+            .field("mute", &Enabled(li.mute))
+            .field("both", &Enabled(li.both))
+            .finish()
+    }
+}
+
+impl fmt::Display for LineIn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for LineIn {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let li = decode::line_in(self.data);
+
+        defmt::write!(
+            f,
+            "LineIn {{ volume: {}, mute: {}, both: {} }}",
+            HalfDb(li.gain_half_db),
+            Enabled(li.mute),
+            Enabled(li.both)
+        )
+    }
+}
@@ -0,0 +1,302 @@
+//! I²C transport over the Linux i2c-dev interface
+//!
+//! [`LinuxI2c`] implements the embedded-hal `I2c` trait on a `/dev/i2c-N` adapter, so the
+//! [`driver`](crate::driver) can run on a single-board computer with the codec on its I²C pins.
+//! Requires the `linux` feature.
+//!
+//! ```no_run
+//! # use wm8731::driver::Wm8731;
+//! # use wm8731::linux::LinuxI2c;
+//! let i2c = LinuxI2c::open_bus(1).expect("no I²C bus 1");
+//! let mut codec = Wm8731::new(i2c);
+//! codec.power_down(|c| c.power_off().power_on())?;
+//! # Ok::<(), wm8731::driver::Error<wm8731::linux::Error>>(())
+//! ```
+//!
+//! Adapters that support plain I²C transfers get each transaction as one combined transfer.
+//! SMBus-only adapters, such as the `i2c-stub` kernel module, get each 16-bit control word as an
+//! SMBus byte data write, with the first byte as the command; that is all the codec needs, so the
+//! transport can be tested without real hardware with `modprobe i2c-stub chip_addr=0x1a`.
+//!
+//! To record the control words instead, [`LinuxI2c::mock_file`] appends the bytes of each write to
+//! a plain file.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::format;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::vec::Vec;
+
+use embedded_hal::i2c::{self, I2c, NoAcknowledgeSource, Operation, SevenBitAddress};
+
+// From linux/i2c-dev.h and linux/i2c.h
+const I2C_SLAVE: libc::c_ulong = 0x0703;
+const I2C_FUNCS: libc::c_ulong = 0x0705;
+const I2C_RDWR: libc::c_ulong = 0x0707;
+const I2C_SMBUS: libc::c_ulong = 0x0720;
+const I2C_M_RD: u16 = 0x0001;
+const I2C_RDWR_IOCTL_MAX_MSGS: usize = 42;
+const I2C_FUNC_I2C: libc::c_ulong = 0x0000_0001;
+const I2C_FUNC_SMBUS_WRITE_BYTE_DATA: libc::c_ulong = 0x0010_0000;
+const I2C_SMBUS_WRITE: u8 = 0;
+const I2C_SMBUS_BYTE_DATA: u32 = 2;
+const I2C_SMBUS_BLOCK_MAX: usize = 32;
+
+#[repr(C)]
+struct I2cMsg {
+    addr: u16,
+    flags: u16,
+    len: u16,
+    buf: *mut u8,
+}
+
+#[repr(C)]
+struct I2cRdwrIoctlData {
+    msgs: *mut I2cMsg,
+    nmsgs: u32,
+}
+
+#[repr(C)]
+union I2cSmbusData {
+    byte: u8,
+    word: u16,
+    block: [u8; I2C_SMBUS_BLOCK_MAX + 2],
+}
+
+#[repr(C)]
+struct I2cSmbusIoctlData {
+    read_write: u8,
+    command: u8,
+    size: u32,
+    data: *mut I2cSmbusData,
+}
+
+/// Errors reported by the i2c-dev transport
+#[derive(Debug)]
+pub enum Error {
+    /// The adapter or file returned an error
+    Io(io::Error),
+    /// An operation's buffer is longer than an i2c-dev message can hold
+    MessageLength(usize),
+    /// A transaction has more operations than the adapter accepts at once
+    Operations(usize),
+    /// The adapter only supports SMBus transfers, and the transaction is not a 2-byte write
+    Smbus,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::MessageLength(len) => write!(f, "{} byte message is too long", len),
+            Error::Operations(count) => write!(f, "{} operations in one transaction", count),
+            Error::Smbus => f.write_str("the SMBus adapter only supports 2-byte writes"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl i2c::Error for Error {
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            // i2c-dev drivers report a missing ACK as ENXIO or EREMOTEIO
+            Error::Io(e) => match e.raw_os_error() {
+                Some(libc::ENXIO) | Some(libc::EREMOTEIO) => {
+                    i2c::ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)
+                }
+                Some(libc::EAGAIN) => i2c::ErrorKind::ArbitrationLoss,
+                _ => i2c::ErrorKind::Other,
+            },
+            _ => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+/// How transactions reach the bus
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    /// Combined transfers with `I2C_RDWR`
+    I2c,
+    /// SMBus byte data writes with `I2C_SMBUS`, to the address last set with `I2C_SLAVE`
+    Smbus { address: Option<u8> },
+    /// Reads and writes of a plain file standing in for an adapter
+    MockFile,
+}
+
+/// An I²C bus behind a Linux i2c-dev device file
+#[derive(Debug)]
+pub struct LinuxI2c {
+    file: File,
+    mode: Mode,
+}
+
+impl LinuxI2c {
+    /// Open an i2c-dev adapter, such as `/dev/i2c-1`
+    ///
+    /// Fails if the file is not an i2c-dev adapter.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_file(OpenOptions::new().read(true).write(true).open(path)?)
+    }
+
+    /// Open the adapter for bus `bus`, `/dev/i2c-<bus>`
+    pub fn open_bus(bus: u32) -> io::Result<Self> {
+        Self::open(format!("/dev/i2c-{}", bus))
+    }
+
+    /// Use an open i2c-dev adapter as the bus
+    ///
+    /// Fails with `ENOTTY` if the file is not an i2c-dev adapter, or `EOPNOTSUPP` if the adapter
+    /// supports neither plain I²C transfers nor SMBus byte data writes.
+    pub fn from_file(file: File) -> io::Result<Self> {
+        let mut funcs: libc::c_ulong = 0;
+        // Safety: I2C_FUNCS writes the adapter's functionality flags to `funcs`
+        if unsafe { libc::ioctl(file.as_raw_fd(), I2C_FUNCS as _, &mut funcs) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mode = if funcs & I2C_FUNC_I2C != 0 {
+            Mode::I2c
+        } else if funcs & I2C_FUNC_SMBUS_WRITE_BYTE_DATA != 0 {
+            Mode::Smbus { address: None }
+        } else {
+            return Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+        };
+
+        Ok(LinuxI2c { file, mode })
+    }
+
+    /// Use a plain file as a mock device, for tests without an adapter
+    ///
+    /// Each write goes straight to the file and each read comes from it, whatever the address,
+    /// so the file records the control words the driver sent.
+    pub fn mock_file(file: File) -> Self {
+        LinuxI2c {
+            file,
+            mode: Mode::MockFile,
+        }
+    }
+
+    /// The underlying file
+    pub fn release(self) -> File {
+        self.file
+    }
+
+    /// Run the operations as one combined transfer with repeated starts
+    fn rdwr(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        if operations.len() > I2C_RDWR_IOCTL_MAX_MSGS {
+            return Err(Error::Operations(operations.len()));
+        }
+
+        let mut msgs = Vec::with_capacity(operations.len());
+        for operation in operations.iter_mut() {
+            let (flags, buf) = match operation {
+                Operation::Read(buf) => (I2C_M_RD, buf.as_mut_ptr()),
+                // The kernel only reads from write buffers
+                Operation::Write(buf) => (0, buf.as_ptr() as *mut u8),
+            };
+            let len = operation_len(operation);
+            msgs.push(I2cMsg {
+                addr: u16::from(address),
+                flags,
+                len: u16::try_from(len).map_err(|_| Error::MessageLength(len))?,
+                buf,
+            });
+        }
+
+        let mut data = I2cRdwrIoctlData {
+            msgs: msgs.as_mut_ptr(),
+            nmsgs: msgs.len() as u32,
+        };
+        // Safety: each message points at a buffer that outlives the call and holds `len` bytes
+        if unsafe { libc::ioctl(self.file.as_raw_fd(), I2C_RDWR as _, &mut data) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(())
+    }
+
+    /// Send a 2-byte write as an SMBus byte data write: the first byte is the command
+    fn smbus(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), Error> {
+        let (command, value) = match operations {
+            [Operation::Write([command, value])] => (*command, *value),
+            _ => return Err(Error::Smbus),
+        };
+
+        let slave = Mode::Smbus {
+            address: Some(address),
+        };
+        let fd = self.file.as_raw_fd();
+        if self.mode != slave {
+            // Safety: I2C_SLAVE takes the 7-bit address as its argument
+            if unsafe { libc::ioctl(fd, I2C_SLAVE as _, libc::c_ulong::from(address)) } < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            self.mode = slave;
+        }
+
+        let mut data = I2cSmbusData { byte: value };
+        let mut args = I2cSmbusIoctlData {
+            read_write: I2C_SMBUS_WRITE,
+            command,
+            size: I2C_SMBUS_BYTE_DATA,
+            data: &mut data,
+        };
+        // Safety: `data` outlives the call, and a byte data write only reads its first byte
+        if unsafe { libc::ioctl(fd, I2C_SMBUS as _, &mut args) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(())
+    }
+}
+
+fn operation_len(operation: &Operation<'_>) -> usize {
+    match operation {
+        Operation::Read(buf) => buf.len(),
+        Operation::Write(buf) => buf.len(),
+    }
+}
+
+impl i2c::ErrorType for LinuxI2c {
+    type Error = Error;
+}
+
+impl I2c for LinuxI2c {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        match self.mode {
+            Mode::I2c => self.rdwr(address, operations),
+            Mode::Smbus { .. } => self.smbus(address, operations),
+            Mode::MockFile => {
+                for operation in operations {
+                    match operation {
+                        Operation::Read(buf) => self.file.read_exact(buf)?,
+                        Operation::Write(buf) => self.file.write_all(buf)?,
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,6 +1,10 @@
 //! Configuration for power to different parts of the device
 
+use core::fmt;
+
 use crate::bitmask::BitMask;
+use crate::decode;
+use crate::display::OnOff;
 
 pub struct PowerOnOff<'a> {
     index: u16,
@@ -23,7 +27,7 @@ impl<'a> PowerOnOff<'a> {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Copy, Clone)]
 pub struct PowerDown {
     pub(crate) data: u16,
 }
@@ -36,42 +40,86 @@ impl PowerDown {
     }
 
     /// Line input
-    pub fn line_input(&mut self) -> PowerOnOff {
+    pub fn line_input(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(0, &mut self.data)
     }
 
     /// Microphone input and bias
-    pub fn mic(&mut self) -> PowerOnOff {
+    pub fn mic(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(1, &mut self.data)
     }
 
     /// ADC
-    pub fn adc(&mut self) -> PowerOnOff {
+    pub fn adc(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(2, &mut self.data)
     }
 
     /// DAC
-    pub fn dac(&mut self) -> PowerOnOff {
+    pub fn dac(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(3, &mut self.data)
     }
 
     /// Outputs
-    pub fn output(&mut self) -> PowerOnOff {
+    pub fn output(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(4, &mut self.data)
     }
 
     /// Oscillator
-    pub fn oscillator(&mut self) -> PowerOnOff {
+    pub fn oscillator(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(5, &mut self.data)
     }
 
     /// CLKOUT
-    pub fn clock_output(&mut self) -> PowerOnOff {
+    pub fn clock_output(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(6, &mut self.data)
     }
 
     /// POWEROFF mode
-    pub fn power_off(&mut self) -> PowerOnOff {
+    pub fn power_off(&mut self) -> PowerOnOff<'_> {
         PowerOnOff::new(7, &mut self.data)
     }
 }
+
+impl fmt::Debug for PowerDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pd = decode::power_down(self.data);
+
+        f.debug_struct("PowerDown")
+            .field("line_input", &OnOff(pd.line_input))
+            .field("mic", &OnOff(pd.mic))
+            .field("adc", &OnOff(pd.adc))
+            .field("dac", &OnOff(pd.dac))
+            .field("output", &OnOff(pd.output))
+            .field("oscillator", &OnOff(pd.oscillator))
+            .field("clock_output", &OnOff(pd.clock_output))
+            .field("chip", &OnOff(pd.chip))
+            .finish()
+    }
+}
+
+impl fmt::Display for PowerDown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for PowerDown {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let pd = decode::power_down(self.data);
+
+        defmt::write!(
+            f,
+            "PowerDown {{ line_input: {}, mic: {}, adc: {}, dac: {}, output: {}, \
+             oscillator: {}, clock_output: {}, chip: {} }}",
+            OnOff(pd.line_input),
+            OnOff(pd.mic),
+            OnOff(pd.adc),
+            OnOff(pd.dac),
+            OnOff(pd.output),
+            OnOff(pd.oscillator),
+            OnOff(pd.clock_output),
+            OnOff(pd.chip)
+        )
+    }
+}
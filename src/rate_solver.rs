@@ -0,0 +1,294 @@
+//! Pick sampling control settings from the master clock and desired sample rates
+//!
+//! Which combination of USB/normal mode, base over-sampling rate, sample rate code and core
+//! clock divider gives a pair of ADC/DAC rates depends on the MCLK frequency. [`solve`] looks the
+//! combination up in the datasheet tables and returns a ready [`Sampling`] value.
+//!
+//! ```
+//! # use wm8731::rate_solver::{solve, SampleRate};
+//! # use wm8731::WM8731;
+//! let sampling = solve(12_288_000, SampleRate::Hz48000, SampleRate::Hz48000).unwrap();
+//! let register = WM8731::sampling(|s| *s = sampling);
+//! assert_eq!(register.value, 0b0_0000_0000);
+//! ```
+
+use core::fmt;
+
+use crate::sampling::Sampling;
+use crate::RateError;
+use crate::SamplingRate;
+
+/// Sample rates supported by the ADC and DAC
+///
+/// In USB mode the rates are derived from a 12MHz MCLK, so [`Hz44100`](Self::Hz44100) and
+/// [`Hz88200`](Self::Hz88200) are really 44.118kHz and 88.235kHz.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SampleRate {
+    Hz8000,
+    /// 8.018kHz, derived from a 44.1kHz-family MCLK in normal mode
+    Hz8018,
+    /// 8.021kHz, derived from a 12MHz MCLK in USB mode
+    Hz8021,
+    Hz32000,
+    Hz44100,
+    Hz48000,
+    Hz88200,
+    Hz96000,
+}
+
+impl SampleRate {
+    /// Human-readable name, as printed by `Display`
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            SampleRate::Hz8000 => "8kHz",
+            SampleRate::Hz8018 => "8.018kHz",
+            SampleRate::Hz8021 => "8.021kHz",
+            SampleRate::Hz32000 => "32kHz",
+            SampleRate::Hz44100 => "44.1kHz",
+            SampleRate::Hz48000 => "48kHz",
+            SampleRate::Hz88200 => "88.2kHz",
+            SampleRate::Hz96000 => "96kHz",
+        }
+    }
+}
+
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A row of the datasheet sample rate tables
+#[derive(Copy, Clone)]
+struct Entry {
+    adc: SampleRate,
+    dac: SampleRate,
+    sample_rate: fn(&mut SamplingRate),
+}
+
+/// Normal mode with a 12.288MHz or 18.432MHz MCLK
+const NORMAL_48: [Entry; 6] = [
+    Entry {
+        adc: SampleRate::Hz48000,
+        dac: SampleRate::Hz48000,
+        sample_rate: |r| r.adc_48().dac_48(),
+    },
+    Entry {
+        adc: SampleRate::Hz48000,
+        dac: SampleRate::Hz8000,
+        sample_rate: |r| r.adc_48().dac_8(),
+    },
+    Entry {
+        adc: SampleRate::Hz8000,
+        dac: SampleRate::Hz48000,
+        sample_rate: |r| r.adc_8().dac_48(),
+    },
+    Entry {
+        adc: SampleRate::Hz8000,
+        dac: SampleRate::Hz8000,
+        sample_rate: |r| r.adc_8().dac_8(),
+    },
+    Entry {
+        adc: SampleRate::Hz32000,
+        dac: SampleRate::Hz32000,
+        sample_rate: |r| r.adc_32().dac_32(),
+    },
+    Entry {
+        adc: SampleRate::Hz96000,
+        dac: SampleRate::Hz96000,
+        sample_rate: |r| r.adc_96().dac_96(),
+    },
+];
+
+/// Normal mode with an 11.2896MHz or 16.9344MHz MCLK
+const NORMAL_441: [Entry; 5] = [
+    Entry {
+        adc: SampleRate::Hz44100,
+        dac: SampleRate::Hz44100,
+        sample_rate: |r| r.adc_441().dac_441(),
+    },
+    Entry {
+        adc: SampleRate::Hz44100,
+        dac: SampleRate::Hz8018,
+        sample_rate: |r| r.adc_441().dac_8018(),
+    },
+    Entry {
+        adc: SampleRate::Hz8018,
+        dac: SampleRate::Hz44100,
+        sample_rate: |r| r.adc_8018().dac_441(),
+    },
+    Entry {
+        adc: SampleRate::Hz8018,
+        dac: SampleRate::Hz8018,
+        sample_rate: |r| r.adc_8018().dac_8018(),
+    },
+    Entry {
+        adc: SampleRate::Hz88200,
+        dac: SampleRate::Hz88200,
+        sample_rate: |r| r.adc_882().dac_882(),
+    },
+];
+
+/// USB mode with a 12MHz MCLK
+const USB: [Entry; 11] = [
+    Entry {
+        adc: SampleRate::Hz48000,
+        dac: SampleRate::Hz48000,
+        sample_rate: |r| r.usb().adc_48().dac_48(),
+    },
+    Entry {
+        adc: SampleRate::Hz44100,
+        dac: SampleRate::Hz44100,
+        sample_rate: |r| r.usb().adc_441().dac_441(),
+    },
+    Entry {
+        adc: SampleRate::Hz48000,
+        dac: SampleRate::Hz8000,
+        sample_rate: |r| r.usb().adc_48().dac_8(),
+    },
+    Entry {
+        adc: SampleRate::Hz44100,
+        dac: SampleRate::Hz8021,
+        sample_rate: |r| r.usb().adc_441().dac_8021(),
+    },
+    Entry {
+        adc: SampleRate::Hz8000,
+        dac: SampleRate::Hz48000,
+        sample_rate: |r| r.usb().adc_8().dac_48(),
+    },
+    Entry {
+        adc: SampleRate::Hz8021,
+        dac: SampleRate::Hz44100,
+        sample_rate: |r| r.usb().adc_8021().dac_441(),
+    },
+    Entry {
+        adc: SampleRate::Hz8000,
+        dac: SampleRate::Hz8000,
+        sample_rate: |r| r.usb().adc_8().dac_8(),
+    },
+    Entry {
+        adc: SampleRate::Hz8021,
+        dac: SampleRate::Hz8021,
+        sample_rate: |r| r.usb().adc_8021().dac_8021(),
+    },
+    Entry {
+        adc: SampleRate::Hz32000,
+        dac: SampleRate::Hz32000,
+        sample_rate: |r| r.usb().adc_32().dac_32(),
+    },
+    Entry {
+        adc: SampleRate::Hz96000,
+        dac: SampleRate::Hz96000,
+        sample_rate: |r| r.usb().adc_96().dac_96(),
+    },
+    Entry {
+        adc: SampleRate::Hz88200,
+        dac: SampleRate::Hz88200,
+        sample_rate: |r| r.usb().adc_882().dac_882(),
+    },
+];
+
+/// How a given MCLK frequency is used
+struct Clocking {
+    usb: bool,
+    /// BOSR for normal mode (384fs rather than 256fs)
+    bosr: bool,
+    divide_by_two: bool,
+    table: &'static [Entry],
+}
+
+fn clocking(mclk_hz: u32) -> Option<Clocking> {
+    let (usb, bosr, table): (bool, bool, &'static [Entry]) = match mclk_hz {
+        12_288_000 | 24_576_000 => (false, false, &NORMAL_48),
+        18_432_000 | 36_864_000 => (false, true, &NORMAL_48),
+        11_289_600 | 22_579_200 => (false, false, &NORMAL_441),
+        16_934_400 | 33_868_800 => (false, true, &NORMAL_441),
+        12_000_000 | 24_000_000 => (true, false, &USB),
+        _ => return None,
+    };
+
+    Some(Clocking {
+        usb,
+        bosr,
+        divide_by_two: mclk_hz > 20_000_000,
+        table,
+    })
+}
+
+/// ADC/DAC rate pairs achievable with a given MCLK
+#[derive(Copy, Clone)]
+pub struct Achievable {
+    table: &'static [Entry],
+}
+
+impl Achievable {
+    /// Every achievable `(adc, dac)` pair
+    pub fn iter(&self) -> impl Iterator<Item = (SampleRate, SampleRate)> {
+        self.table.iter().map(|entry| (entry.adc, entry.dac))
+    }
+}
+
+impl fmt::Debug for Achievable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Achievable {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "[");
+        for (i, (adc, dac)) in self.iter().enumerate() {
+            if i > 0 {
+                defmt::write!(f, ", ");
+            }
+            defmt::write!(f, "({}, {})", adc, dac);
+        }
+        defmt::write!(f, "]")
+    }
+}
+
+impl PartialEq for Achievable {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for Achievable {}
+
+/// Sampling control settings for the given MCLK frequency and ADC/DAC sample rates
+///
+/// Supported MCLK frequencies are 12.288MHz, 11.2896MHz, 18.432MHz, 16.9344MHz and 12MHz (USB
+/// mode), as well as twice each of those, which enables the core clock divider.
+pub fn solve(mclk_hz: u32, adc: SampleRate, dac: SampleRate) -> Result<Sampling, RateError> {
+    let clocking = clocking(mclk_hz).ok_or(RateError::UnsupportedMclk(mclk_hz))?;
+    let entry = clocking
+        .table
+        .iter()
+        .find(|entry| entry.adc == adc && entry.dac == dac)
+        .ok_or(RateError::Unachievable(Achievable {
+            table: clocking.table,
+        }))?;
+
+    let mut s = Sampling::new();
+    // USB mode rates select USB mode and BOSR themselves
+    if !clocking.usb {
+        s.usb_normal().normal();
+        if clocking.bosr {
+            s.base_oversampling_rate().normal_384();
+        } else {
+            s.base_oversampling_rate().normal_256();
+        }
+    }
+    (entry.sample_rate)(&mut s.sample_rate());
+    if clocking.divide_by_two {
+        s.core_clock_divider_select().divided_by_two();
+    } else {
+        s.core_clock_divider_select().normal();
+    }
+
+    Ok(s)
+}
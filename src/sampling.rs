@@ -1,6 +1,10 @@
 //! Configuration for sampling
 
+use core::fmt;
+
 use crate::bitmask::BitMask;
+use crate::decode;
+use crate::display::{divider_str, oversampling_str, usb_normal_str, Plain};
 use crate::SamplingRate;
 
 pub struct UsbNormal<'a> {
@@ -74,7 +78,7 @@ impl<'a> Oversampling<'a> {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Copy, Clone)]
 pub struct Sampling {
     pub(crate) data: u16,
 }
@@ -87,27 +91,101 @@ impl Sampling {
     }
 
     /// USB/normal mode select
-    pub fn usb_normal(&mut self) -> UsbNormal {
+    pub fn usb_normal(&mut self) -> UsbNormal<'_> {
         UsbNormal::new(0, &mut self.data)
     }
 
     /// Base over-sampling rate
-    pub fn base_oversampling_rate(&mut self) -> Oversampling {
+    pub fn base_oversampling_rate(&mut self) -> Oversampling<'_> {
         Oversampling::new(1, &mut self.data)
     }
 
     /// ADC and DAC sample rate
-    pub fn sample_rate(&mut self) -> SamplingRate {
+    pub fn sample_rate(&mut self) -> SamplingRate<'_> {
         SamplingRate::new(2, &mut self.data)
     }
 
     /// Core clock divider select
-    pub fn core_clock_divider_select(&mut self) -> ClockDivider {
+    pub fn core_clock_divider_select(&mut self) -> ClockDivider<'_> {
         ClockDivider::new(6, &mut self.data)
     }
 
     /// CLKOUT divider select
-    pub fn clock_out_divider_select(&mut self) -> ClockDivider {
+    pub fn clock_out_divider_select(&mut self) -> ClockDivider<'_> {
         ClockDivider::new(7, &mut self.data)
     }
 }
+
+impl fmt::Debug for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let usb = self.data & 1 != 0;
+        let bosr = self.data & 0b10 != 0;
+
+        let mut s = f.debug_struct("Sampling");
+        s.field("usb_normal", &Plain(usb_normal_str(usb))).field(
+            "base_oversampling_rate",
+            &Plain(oversampling_str(usb, bosr)),
+        );
+        match decode::sampling(self.data) {
+            Ok(settings) => s.field(
+                "sample_rate",
+                &format_args!("ADC {}, DAC {}", settings.adc, settings.dac),
+            ),
+            Err(_) => s.field(
+                "sample_rate",
+                &format_args!("reserved ({:#06b})", (self.data >> 2) & 0b1111),
+            ),
+        };
+        s.field(
+            "core_clock_divider_select",
+            &Plain(divider_str(self.data & (1 << 6) != 0)),
+        )
+        .field(
+            "clock_out_divider_select",
+            &Plain(divider_str(self.data & (1 << 7) != 0)),
+        )
+        .finish()
+    }
+}
+
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Sampling {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        let usb = self.data & 1 != 0;
+        let bosr = self.data & 0b10 != 0;
+
+        defmt::write!(
+            f,
+            "Sampling {{ usb_normal: {=str}, base_oversampling_rate: {=str}, ",
+            usb_normal_str(usb),
+            oversampling_str(usb, bosr)
+        );
+        match decode::sampling(self.data) {
+            Ok(settings) => {
+                defmt::write!(
+                    f,
+                    "sample_rate: ADC {=str}, DAC {=str}",
+                    settings.adc.as_str(),
+                    settings.dac.as_str()
+                )
+            }
+            Err(_) => defmt::write!(
+                f,
+                "sample_rate: reserved ({=u16:#06b})",
+                (self.data >> 2) & 0b1111
+            ),
+        }
+        defmt::write!(
+            f,
+            ", core_clock_divider_select: {=str}, clock_out_divider_select: {=str} }}",
+            divider_str(self.data & (1 << 6) != 0),
+            divider_str(self.data & (1 << 7) != 0)
+        )
+    }
+}
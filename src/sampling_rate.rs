@@ -128,6 +128,208 @@ impl<'a> Adc882<'a> {
     }
 }
 
+/// Select USB mode, the base over-sampling rate, and the sample rate code
+///
+/// The USB/normal and BOSR bits sit directly below the sample rate bits.
+fn usb_mode(bitmask: &mut BitMask, index: u16, sample_rate: u16, bosr: bool) {
+    bitmask.set(index - 2);
+    if bosr {
+        bitmask.set(index - 1);
+    } else {
+        bitmask.unset(index - 1);
+    }
+    bitmask.apply(index, 4, sample_rate)
+}
+
+pub struct UsbAdc48<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc48<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc48 { index, bitmask }
+    }
+
+    /// DAC 48kHz, 250fs
+    pub fn dac_48(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b0000, false)
+    }
+
+    /// DAC 8kHz, 250fs
+    pub fn dac_8(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b0001, false)
+    }
+}
+
+pub struct UsbAdc8<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc8<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc8 { index, bitmask }
+    }
+
+    /// DAC 48kHz, 250fs
+    pub fn dac_48(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b0010, false)
+    }
+
+    /// DAC 8kHz, 250fs
+    pub fn dac_8(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b0011, false)
+    }
+}
+
+pub struct UsbAdc32<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc32<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc32 { index, bitmask }
+    }
+
+    /// DAC 32kHz, 250fs
+    pub fn dac_32(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b0110, false)
+    }
+}
+
+pub struct UsbAdc96<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc96<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc96 { index, bitmask }
+    }
+
+    /// DAC 96kHz, 125fs
+    pub fn dac_96(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b0111, false)
+    }
+}
+
+pub struct UsbAdc441<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc441<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc441 { index, bitmask }
+    }
+
+    /// DAC 44.118kHz, 272fs
+    pub fn dac_441(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b1000, true)
+    }
+
+    /// DAC 8.021kHz, 272fs
+    pub fn dac_8021(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b1001, true)
+    }
+}
+
+pub struct UsbAdc8021<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc8021<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc8021 { index, bitmask }
+    }
+
+    /// DAC 44.118kHz, 272fs
+    pub fn dac_441(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b1010, true)
+    }
+
+    /// DAC 8.021kHz, 272fs
+    pub fn dac_8021(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b1011, true)
+    }
+}
+
+pub struct UsbAdc882<'a> {
+    index: u16,
+    bitmask: BitMask<'a>,
+}
+
+impl<'a> UsbAdc882<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        let bitmask = BitMask::new(data);
+        UsbAdc882 { index, bitmask }
+    }
+
+    /// DAC 88.235kHz, 136fs
+    pub fn dac_882(&mut self) {
+        usb_mode(&mut self.bitmask, self.index, 0b1111, true)
+    }
+}
+
+/// Sample rates available in USB mode, with a 12MHz MCLK
+///
+/// Selecting a rate also switches to USB mode and sets the base over-sampling rate the datasheet
+/// requires for it, so there is no need to call `usb_normal` or `base_oversampling_rate`.
+pub struct UsbSamplingRate<'a> {
+    index: u16,
+    data: &'a mut u16,
+}
+
+impl<'a> UsbSamplingRate<'a> {
+    pub fn new(index: u16, data: &'a mut u16) -> Self {
+        UsbSamplingRate { index, data }
+    }
+
+    /// ADC 48kHz
+    pub fn adc_48(&mut self) -> UsbAdc48<'_> {
+        UsbAdc48::new(self.index, self.data)
+    }
+
+    /// ADC 8kHz
+    pub fn adc_8(&mut self) -> UsbAdc8<'_> {
+        UsbAdc8::new(self.index, self.data)
+    }
+
+    /// ADC 32kHz
+    pub fn adc_32(&mut self) -> UsbAdc32<'_> {
+        UsbAdc32::new(self.index, self.data)
+    }
+
+    /// ADC 96kHz
+    pub fn adc_96(&mut self) -> UsbAdc96<'_> {
+        UsbAdc96::new(self.index, self.data)
+    }
+
+    /// ADC 44.118kHz
+    pub fn adc_441(&mut self) -> UsbAdc441<'_> {
+        UsbAdc441::new(self.index, self.data)
+    }
+
+    /// ADC 8.021kHz
+    pub fn adc_8021(&mut self) -> UsbAdc8021<'_> {
+        UsbAdc8021::new(self.index, self.data)
+    }
+
+    /// ADC 88.235kHz
+    pub fn adc_882(&mut self) -> UsbAdc882<'_> {
+        UsbAdc882::new(self.index, self.data)
+    }
+}
+
 pub struct SamplingRate<'a> {
     index: u16,
     data: &'a mut u16,
@@ -138,31 +340,36 @@ impl<'a> SamplingRate<'a> {
         SamplingRate { index, data }
     }
 
-    pub fn adc_48(&mut self) -> Adc48 {
+    pub fn adc_48(&mut self) -> Adc48<'_> {
         Adc48::new(self.index, self.data)
     }
 
-    pub fn adc_8(&mut self) -> Adc8 {
+    pub fn adc_8(&mut self) -> Adc8<'_> {
         Adc8::new(self.index, self.data)
     }
 
-    pub fn adc_32(&mut self) -> Adc32 {
+    pub fn adc_32(&mut self) -> Adc32<'_> {
         Adc32::new(self.index, self.data)
     }
 
-    pub fn adc_96(&mut self) -> Adc96 {
+    pub fn adc_96(&mut self) -> Adc96<'_> {
         Adc96::new(self.index, self.data)
     }
 
-    pub fn adc_441(&mut self) -> Adc441 {
+    pub fn adc_441(&mut self) -> Adc441<'_> {
         Adc441::new(self.index, self.data)
     }
 
-    pub fn adc_8018(&mut self) -> Adc8018 {
+    pub fn adc_8018(&mut self) -> Adc8018<'_> {
         Adc8018::new(self.index, self.data)
     }
 
-    pub fn adc_882(&mut self) -> Adc882 {
+    pub fn adc_882(&mut self) -> Adc882<'_> {
         Adc882::new(self.index, self.data)
     }
+
+    /// USB mode sample rates
+    pub fn usb(&mut self) -> UsbSamplingRate<'_> {
+        UsbSamplingRate::new(self.index, self.data)
+    }
 }
@@ -0,0 +1,142 @@
+//! Pop-free power sequences
+//!
+//! The datasheet prescribes the order of power down, configuration and active writes that
+//! avoids audible pops when the codec powers up or down. The functions here produce that order as
+//! a series of [`Step`]s; waiting is expressed as a [`Step::Delay`] rather than done here, so the
+//! steps can be replayed by any transport. Each sequence activates or deactivates the interface,
+//! so the [`driver`](crate::driver) runs them with
+//! [`run_power_up`](crate::driver::Wm8731::run_power_up),
+//! [`run_power_down`](crate::driver::Wm8731::run_power_down) and
+//! [`run_standby`](crate::driver::Wm8731::run_standby), which track that in its type.
+//!
+//! ```
+//! # use wm8731::sequence::{power_up, Step};
+//! # use wm8731::WM8731;
+//! # use wm8731::power_down::PowerDown;
+//! let mut power = PowerDown::new();
+//! power.line_input().power_on();
+//! power.adc().power_on();
+//! power.dac().power_on();
+//! power.output().power_on();
+//! power.power_off().power_on();
+//!
+//! let configuration = [WM8731::analog_audio_path(|c| c.dac_select().select())];
+//!
+//! for step in power_up(power, &configuration, 500) {
+//!     match step {
+//!         Step::Write(register) => { /* write it */ }
+//!         Step::Delay { ms } => { /* wait */ }
+//!     }
+//! }
+//! ```
+
+use core::iter::once;
+
+use crate::digital_audio_path::DigitalAudioPath;
+use crate::power_down::PowerDown;
+use crate::{Register, WM8731};
+
+/// Address of the digital audio path register
+const DIGITAL_AUDIO_PATH: u8 = 5;
+
+/// Address of the power down register
+const POWER_DOWN: u8 = 6;
+
+/// One step of a power sequence
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Step {
+    /// Write a register
+    Write(Register),
+    /// Wait before the next step
+    Delay { ms: u32 },
+}
+
+/// `power` with the outputs powered off
+fn outputs_off(mut power: PowerDown) -> Register {
+    power.output().power_off();
+
+    Register {
+        address: POWER_DOWN,
+        value: power.data,
+    }
+}
+
+/// `path` with the DAC soft muted
+fn dac_muted(mut path: DigitalAudioPath) -> Register {
+    path.dac_mute().enable();
+
+    Register {
+        address: DIGITAL_AUDIO_PATH,
+        value: path.data,
+    }
+}
+
+/// Power up and configure the codec, enabling the outputs last
+///
+/// 1. Power up everything in `power` except the outputs
+/// 2. Write each register in `configuration`, which should not include the power down, active or
+///    reset registers, such as those from
+///    [`CodecConfig::power_up_configuration`](crate::config::CodecConfig::power_up_configuration)
+/// 3. Activate the digital audio interface
+/// 4. Wait `settle_ms` for VMID to charge; this depends on the VMID decoupling capacitor
+/// 5. Power up the outputs as set in `power`
+pub fn power_up(
+    power: PowerDown,
+    configuration: &[Register],
+    settle_ms: u32,
+) -> impl Iterator<Item = Step> + Clone + '_ {
+    once(Step::Write(outputs_off(power)))
+        .chain(configuration.iter().copied().map(Step::Write))
+        .chain(once(Step::Write(WM8731::active().active())))
+        .chain(once(Step::Delay { ms: settle_ms }))
+        .chain(once(Step::Write(Register {
+            address: POWER_DOWN,
+            value: power.data,
+        })))
+}
+
+/// Power the codec down completely
+///
+/// The DAC is soft muted and the outputs are powered off before the interface is deactivated and
+/// the rest of the chip is powered down. `power` and `path` are the current power down and
+/// digital audio path settings.
+pub fn power_down(power: PowerDown, path: DigitalAudioPath) -> [Step; 4] {
+    [
+        Step::Write(dac_muted(path)),
+        Step::Write(outputs_off(power)),
+        Step::Write(WM8731::active().inactive()),
+        Step::Write(WM8731::power_down(|c| {
+            c.line_input().power_off();
+            c.mic().power_off();
+            c.adc().power_off();
+            c.dac().power_off();
+            c.output().power_off();
+            c.oscillator().power_off();
+            c.clock_output().power_off();
+            c.power_off().power_off();
+        })),
+    ]
+}
+
+/// Put the codec in standby
+///
+/// Like [`power_down`], but the references stay powered so the codec can return to operation
+/// with [`power_up`] without waiting as long for VMID to charge.
+pub fn standby(power: PowerDown, path: DigitalAudioPath) -> [Step; 4] {
+    [
+        Step::Write(dac_muted(path)),
+        Step::Write(outputs_off(power)),
+        Step::Write(WM8731::active().inactive()),
+        Step::Write(WM8731::power_down(|c| {
+            c.line_input().power_off();
+            c.mic().power_off();
+            c.adc().power_off();
+            c.dac().power_off();
+            c.output().power_off();
+            c.oscillator().power_off();
+            c.clock_output().power_off();
+            c.power_off().power_on();
+        })),
+    ]
+}
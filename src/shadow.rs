@@ -0,0 +1,293 @@
+//! Shadow copy of the codec's write-only registers
+//!
+//! The WM8731 control interface cannot be read back, so changing a single field means knowing
+//! what was last written to the rest of the register. [`ShadowRegisters`] remembers every value
+//! written and offers read-modify-write versions of the [`WM8731`] builders, which start from
+//! the cached value instead of the reset default.
+//!
+//! ```
+//! # use wm8731::shadow::ShadowRegisters;
+//! let mut shadow = ShadowRegisters::new();
+//!
+//! shadow.digital_audio_path(|c| c.deemphasis().frequency_48());
+//!
+//! // De-emphasis stays at 48kHz
+//! let register = shadow.digital_audio_path(|c| c.dac_mute().disable());
+//! assert_eq!(register.value, 0b0_0000_0110);
+//! ```
+
+use crate::analog_audio_path::AnalogAudioPath;
+use crate::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+use crate::digital_audio_path::DigitalAudioPath;
+use crate::headphone_out::HeadphoneOut;
+use crate::line_in::LineIn;
+use crate::power_down::PowerDown;
+use crate::sampling::Sampling;
+use crate::stereo::{other_channel, StereoWrite, BOTH};
+use crate::{Register, WM8731};
+
+/// Values of registers 0-9 after a reset
+const RESET_VALUES: [u16; 10] = [
+    0b0_1001_0111,
+    0b0_1001_0111,
+    0b0_0111_1001,
+    0b0_0111_1001,
+    0b0_0000_1010,
+    0b0_0000_1000,
+    0b0_1001_1111,
+    0b0_0000_1010,
+    0b0_0000_0000,
+    0b0_0000_0000,
+];
+
+/// Address of the reset register
+const RESET: u8 = 15;
+
+/// Last written value of every register
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ShadowRegisters {
+    values: [u16; 10],
+}
+
+impl ShadowRegisters {
+    /// Shadow registers holding the datasheet reset defaults
+    pub fn new() -> Self {
+        ShadowRegisters {
+            values: RESET_VALUES,
+        }
+    }
+
+    /// Last value written to the register at `address`
+    ///
+    /// Returns `None` for the reset register and for addresses that are not registers.
+    pub fn get(&self, address: u8) -> Option<u16> {
+        self.values.get(address as usize).copied()
+    }
+
+    /// Every register except reset, in address order
+    pub fn registers(&self) -> [Register; 10] {
+        let mut registers = [Register {
+            address: 0,
+            value: 0,
+        }; 10];
+        for (address, register) in registers.iter_mut().enumerate() {
+            register.address = address as u8;
+            register.value = self.values[address];
+        }
+
+        registers
+    }
+
+    /// Remember a register that was written to the codec
+    ///
+    /// Writing the reset register restores the reset defaults. A line input or headphone out
+    /// register written with its "both" bit set also loads the other channel, which keeps its
+    /// own "both" bit. Unknown addresses are ignored.
+    pub fn record(&mut self, register: Register) {
+        if register.address == RESET {
+            self.values = RESET_VALUES;
+        } else if let Some(value) = self.values.get_mut(register.address as usize) {
+            *value = register.value;
+        }
+
+        if register.value & BOTH != 0 {
+            if let Some(other) = other_channel(register.address) {
+                let value = &mut self.values[other as usize];
+                *value = register.value & !BOTH | *value & BOTH;
+            }
+        }
+    }
+
+    /// Record a value built from the cache and return it for writing
+    fn store(&mut self, address: u8, value: u16) -> Register {
+        let register = Register { address, value };
+        self.record(register);
+
+        register
+    }
+
+    /// Record stereo writes and return them for writing
+    fn store_stereo(&mut self, registers: StereoWrite) -> StereoWrite {
+        for register in registers.iter() {
+            self.record(*register);
+        }
+
+        registers
+    }
+
+    /// Left line input control register
+    ///
+    /// Starts from the cached value with the "both" bit cleared, so the other channel is only
+    /// written if `c` enables it.
+    pub fn left_line_in<F: FnOnce(&mut LineIn)>(&mut self, c: F) -> Register {
+        let mut li = LineIn {
+            data: self.values[0] & !BOTH,
+        };
+        c(&mut li);
+
+        self.store(0, li.data)
+    }
+
+    /// Right line input control register
+    ///
+    /// Starts from the cached value with the "both" bit cleared, so the other channel is only
+    /// written if `c` enables it.
+    pub fn right_line_in<F: FnOnce(&mut LineIn)>(&mut self, c: F) -> Register {
+        let mut li = LineIn {
+            data: self.values[1] & !BOTH,
+        };
+        c(&mut li);
+
+        self.store(1, li.data)
+    }
+
+    /// Left headphone out control register
+    ///
+    /// Starts from the cached value with the "both" bit cleared, so the other channel is only
+    /// written if `c` enables it.
+    pub fn left_headphone_out<F: FnOnce(&mut HeadphoneOut)>(&mut self, c: F) -> Register {
+        let mut lho = HeadphoneOut {
+            data: self.values[2] & !BOTH,
+        };
+        c(&mut lho);
+
+        self.store(2, lho.data)
+    }
+
+    /// Right headphone out control register
+    ///
+    /// Starts from the cached value with the "both" bit cleared, so the other channel is only
+    /// written if `c` enables it.
+    pub fn right_headphone_out<F: FnOnce(&mut HeadphoneOut)>(&mut self, c: F) -> Register {
+        let mut rho = HeadphoneOut {
+            data: self.values[3] & !BOTH,
+        };
+        c(&mut rho);
+
+        self.store(3, rho.data)
+    }
+
+    /// Left and right line input control registers, in as few writes as possible
+    ///
+    /// Each channel starts from its cached value. See [`stereo`](crate::stereo) for how the
+    /// "both" bit is used.
+    pub fn stereo_line_in<F: FnOnce(&mut LineIn), G: FnOnce(&mut LineIn)>(
+        &mut self,
+        left: F,
+        right: G,
+    ) -> StereoWrite {
+        let mut lli = LineIn {
+            data: self.values[0],
+        };
+        left(&mut lli);
+        let mut rli = LineIn {
+            data: self.values[1],
+        };
+        right(&mut rli);
+
+        self.store_stereo(StereoWrite::new(0, lli.data, rli.data))
+    }
+
+    /// Left and right headphone out control registers, in as few writes as possible
+    ///
+    /// Each channel starts from its cached value. See [`stereo`](crate::stereo) for how the
+    /// "both" bit is used.
+    pub fn stereo_headphone_out<F: FnOnce(&mut HeadphoneOut), G: FnOnce(&mut HeadphoneOut)>(
+        &mut self,
+        left: F,
+        right: G,
+    ) -> StereoWrite {
+        let mut lho = HeadphoneOut {
+            data: self.values[2],
+        };
+        left(&mut lho);
+        let mut rho = HeadphoneOut {
+            data: self.values[3],
+        };
+        right(&mut rho);
+
+        self.store_stereo(StereoWrite::new(2, lho.data, rho.data))
+    }
+
+    /// Analog audio path control register
+    pub fn analog_audio_path<F: FnOnce(&mut AnalogAudioPath)>(&mut self, c: F) -> Register {
+        let mut aap = AnalogAudioPath {
+            data: self.values[4],
+        };
+        c(&mut aap);
+
+        self.store(4, aap.data)
+    }
+
+    /// Digital audio path control register
+    pub fn digital_audio_path<F: FnOnce(&mut DigitalAudioPath)>(&mut self, c: F) -> Register {
+        let mut dap = DigitalAudioPath {
+            data: self.values[5],
+        };
+        c(&mut dap);
+
+        self.store(5, dap.data)
+    }
+
+    /// Power down control register
+    pub fn power_down<F: FnOnce(&mut PowerDown)>(&mut self, c: F) -> Register {
+        let mut pd = PowerDown {
+            data: self.values[6],
+        };
+        c(&mut pd);
+
+        self.store(6, pd.data)
+    }
+
+    /// Digital audio interface format control register
+    pub fn digital_audio_interface_format<F: FnOnce(&mut DigitalAudioInterfaceFormat)>(
+        &mut self,
+        c: F,
+    ) -> Register {
+        let mut daif = DigitalAudioInterfaceFormat {
+            data: self.values[7],
+        };
+        c(&mut daif);
+
+        self.store(7, daif.data)
+    }
+
+    /// Sampling control register
+    pub fn sampling<F: FnOnce(&mut Sampling)>(&mut self, c: F) -> Register {
+        let mut s = Sampling {
+            data: self.values[8],
+        };
+        c(&mut s);
+
+        self.store(8, s.data)
+    }
+
+    /// Whether the digital audio interface was last activated
+    pub fn is_active(&self) -> bool {
+        self.values[9] & 1 == 1
+    }
+
+    /// Activate the digital audio interface
+    pub fn activate(&mut self) -> Register {
+        let register = WM8731::active().active();
+        self.record(register);
+
+        register
+    }
+
+    /// Deactivate the digital audio interface
+    pub fn deactivate(&mut self) -> Register {
+        let register = WM8731::active().inactive();
+        self.record(register);
+
+        register
+    }
+
+    /// Reset all registers to their defaults
+    pub fn reset(&mut self) -> Register {
+        let register = WM8731::reset();
+        self.record(register);
+
+        register
+    }
+}
@@ -0,0 +1,340 @@
+//! Behavioural model of the codec for host-side testing
+//!
+//! [`Simulator`] stands in for a WM8731 on the control bus. It implements the embedded-hal
+//! `I2c` and `SpiDevice` traits, accepts register writes the way the codec does, and answers
+//! questions about the resulting state, so driver code can be tested without hardware.
+//!
+//! ```
+//! # use wm8731::driver::Wm8731;
+//! # use wm8731::simulator::Simulator;
+//! let mut sim = Simulator::new();
+//!
+//! let mut codec = Wm8731::new(&mut sim);
+//! codec.analog_audio_path(|c| c.dac_select().select())?;
+//! codec.power_down(|c| {
+//!     c.dac().power_on();
+//!     c.output().power_on();
+//!     c.power_off().power_on();
+//! })?;
+//!
+//! assert!(sim.dac_to_output());
+//! # Ok::<(), wm8731::driver::Error<wm8731::simulator::Error>>(())
+//! ```
+
+use core::fmt;
+
+use embedded_hal::i2c::{self, I2c, NoAcknowledgeSource, SevenBitAddress};
+use embedded_hal::spi::{self, SpiDevice};
+
+use crate::config::{
+    AnalogAudioPathConfig, DigitalAudioPathConfig, FormatConfig, HeadphoneOutConfig, Input,
+    LineInConfig, PowerConfig,
+};
+use crate::decode::{self, SamplingSettings};
+use crate::driver::{nack_str, ADDRESS_CSB_LOW};
+use crate::shadow::ShadowRegisters;
+use crate::{DecodeError, Register, RegisterError};
+
+/// Errors the simulated codec reports on the bus
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A transfer was not acknowledged: either it was for another I²C address, or the
+    /// simulator was told to fail with [`Simulator::nack_next`]
+    Nack(NoAcknowledgeSource),
+    /// A transfer was not a single 16-bit control word
+    WordLength(usize),
+    /// The control word does not address a register or overflows it
+    Register(RegisterError),
+    /// The codec's control interface cannot be read
+    Read,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Nack(source) => f.write_str(nack_str(*source)),
+            Error::WordLength(len) => write!(f, "{} byte transfer is not a control word", len),
+            Error::Register(e) => write!(f, "{}", e),
+            Error::Read => f.write_str("the control interface cannot be read"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl i2c::Error for Error {
+    fn kind(&self) -> i2c::ErrorKind {
+        match self {
+            Error::Nack(source) => i2c::ErrorKind::NoAcknowledge(*source),
+            _ => i2c::ErrorKind::Other,
+        }
+    }
+}
+
+impl spi::Error for Error {
+    fn kind(&self) -> spi::ErrorKind {
+        spi::ErrorKind::Other
+    }
+}
+
+/// The bytes of one bus transfer, which may be spread over several write operations
+#[derive(Default)]
+struct Transfer {
+    word: [u8; 2],
+    length: usize,
+}
+
+impl Transfer {
+    fn extend(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            if let Some(slot) = self.word.get_mut(self.length) {
+                *slot = *byte;
+            }
+            self.length += 1;
+        }
+    }
+
+    /// The control word, if the transfer was exactly one
+    fn word(&self) -> Result<[u8; 2], Error> {
+        if self.length == self.word.len() {
+            Ok(self.word)
+        } else {
+            Err(Error::WordLength(self.length))
+        }
+    }
+}
+
+/// A simulated WM8731
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    registers: ShadowRegisters,
+    address: u8,
+    writes: usize,
+    nack_next: bool,
+}
+
+impl Simulator {
+    /// A codec fresh out of reset with the CSB pin tied low
+    pub fn new() -> Self {
+        Self::new_with_address(ADDRESS_CSB_LOW)
+    }
+
+    /// A codec fresh out of reset answering at the given 7-bit I²C address
+    pub fn new_with_address(address: u8) -> Self {
+        Simulator {
+            registers: ShadowRegisters::new(),
+            address,
+            writes: 0,
+            nack_next: false,
+        }
+    }
+
+    /// Fail the next transfer with a data NACK, as a codec with a bad connection would
+    pub fn nack_next(&mut self) {
+        self.nack_next = true;
+    }
+
+    /// Number of register writes accepted
+    pub fn writes(&self) -> usize {
+        self.writes
+    }
+
+    /// Accept a register write as the codec would
+    ///
+    /// Writing the reset register restores the reset defaults. Writing a line input or
+    /// headphone out register with its "both" bit set loads the same settings into the other
+    /// channel, leaving the other channel's own "both" bit alone.
+    pub fn write(&mut self, register: Register) {
+        self.writes += 1;
+        self.registers.record(register);
+    }
+
+    /// Accept one control bus transfer
+    fn receive(&mut self, transfer: &Transfer) -> Result<(), Error> {
+        if self.nack_next {
+            self.nack_next = false;
+            return Err(Error::Nack(NoAcknowledgeSource::Data));
+        }
+        let word = transfer.word()?;
+        let register = Register::from_bytes(word).map_err(Error::Register)?;
+        self.write(register);
+
+        Ok(())
+    }
+
+    /// Current value of the register at `address`
+    ///
+    /// Returns `None` for the reset register and for addresses that are not registers.
+    pub fn register(&self, address: u8) -> Option<u16> {
+        self.registers.get(address)
+    }
+
+    /// Value of a register that is known to exist
+    fn value(&self, address: u8) -> u16 {
+        self.registers.get(address).unwrap_or(0)
+    }
+
+    /// Every register except reset, in address order
+    pub fn registers(&self) -> [Register; 10] {
+        self.registers.registers()
+    }
+
+    /// Left line input settings
+    pub fn left_line_in(&self) -> LineInConfig {
+        decode::line_in(self.value(0))
+    }
+
+    /// Right line input settings
+    pub fn right_line_in(&self) -> LineInConfig {
+        decode::line_in(self.value(1))
+    }
+
+    /// Left headphone out settings
+    pub fn left_headphone_out(&self) -> HeadphoneOutConfig {
+        decode::headphone_out(self.value(2))
+    }
+
+    /// Right headphone out settings
+    pub fn right_headphone_out(&self) -> HeadphoneOutConfig {
+        decode::headphone_out(self.value(3))
+    }
+
+    /// Analog audio path settings
+    pub fn analog_audio_path(&self) -> AnalogAudioPathConfig {
+        decode::analog_audio_path(self.value(4))
+    }
+
+    /// Digital audio path settings
+    pub fn digital_audio_path(&self) -> DigitalAudioPathConfig {
+        decode::digital_audio_path(self.value(5))
+    }
+
+    /// Which parts of the codec are powered
+    pub fn power(&self) -> PowerConfig {
+        decode::power_down(self.value(6))
+    }
+
+    /// Digital audio interface settings
+    pub fn format(&self) -> FormatConfig {
+        decode::digital_audio_interface_format(self.value(7))
+    }
+
+    /// Sampling control settings; fails if a reserved sample rate code was written
+    pub fn sampling(&self) -> Result<SamplingSettings, DecodeError> {
+        decode::sampling(self.value(8))
+    }
+
+    /// Whether the digital audio interface is active
+    pub fn is_active(&self) -> bool {
+        self.registers.is_active()
+    }
+
+    /// Whether the DAC is routed to the outputs, with the DAC, outputs and chip powered
+    pub fn dac_to_output(&self) -> bool {
+        let power = self.power();
+
+        self.analog_audio_path().dac_select && power.chip && power.dac && power.output
+    }
+
+    /// Whether the line input is routed to the ADC, with at least one channel unmuted and the
+    /// line input, ADC and chip powered
+    pub fn line_in_to_adc(&self) -> bool {
+        let power = self.power();
+        let unmuted = !self.left_line_in().mute || !self.right_line_in().mute;
+
+        self.analog_audio_path().input == Input::LineInput
+            && unmuted
+            && power.chip
+            && power.line_input
+            && power.adc
+    }
+
+    /// Whether the microphone is routed to the ADC, with the microphone, ADC and chip powered
+    pub fn mic_to_adc(&self) -> bool {
+        let path = self.analog_audio_path();
+        let power = self.power();
+
+        path.input == Input::Mic && !path.mute_mic && power.chip && power.mic && power.adc
+    }
+
+    /// Whether the line input bypasses the ADC and DAC to the outputs, with the line input,
+    /// outputs and chip powered
+    pub fn bypass_to_output(&self) -> bool {
+        let power = self.power();
+
+        self.analog_audio_path().bypass && power.chip && power.line_input && power.output
+    }
+}
+
+impl i2c::ErrorType for Simulator {
+    type Error = Error;
+}
+
+impl I2c for Simulator {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != self.address {
+            return Err(Error::Nack(NoAcknowledgeSource::Address));
+        }
+
+        // Adjacent writes are sent as one transfer, so together they make up one control word
+        let mut transfer = None;
+        for operation in operations.iter() {
+            match operation {
+                i2c::Operation::Write(bytes) => {
+                    transfer.get_or_insert_with(Transfer::default).extend(bytes)
+                }
+                i2c::Operation::Read(_) => {
+                    if let Some(transfer) = transfer.take() {
+                        self.receive(&transfer)?;
+                    }
+                    return Err(Error::Read);
+                }
+            }
+        }
+
+        match transfer {
+            Some(transfer) => self.receive(&transfer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl spi::ErrorType for Simulator {
+    type Error = Error;
+}
+
+impl SpiDevice for Simulator {
+    // Each transaction is one CSB frame, latched when chip select is released
+    fn transaction(
+        &mut self,
+        operations: &mut [spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut transfer = Transfer::default();
+        for operation in operations.iter() {
+            match operation {
+                spi::Operation::Write(bytes) => transfer.extend(bytes),
+                spi::Operation::DelayNs(_) => {}
+                _ => return Err(Error::Read),
+            }
+        }
+
+        self.receive(&transfer)
+    }
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for Simulator {
+    async fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        I2c::transaction(self, address, operations)
+    }
+}
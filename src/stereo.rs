@@ -0,0 +1,96 @@
+//! Writing the left and right channels together
+//!
+//! The line input and headphone out registers each have a "both" bit: when a write to one
+//! channel has it set, the codec loads the same settings into the other channel too. The
+//! stereo functions here use it whenever the channels match, and otherwise write each channel
+//! with the bit cleared so neither write spills over into the other channel.
+//!
+//! ```
+//! # use wm8731::WM8731;
+//! let registers = WM8731::stereo_line_in(|c| c.volume().nearest_dB(3), |c| c.volume().nearest_dB(3));
+//! assert_eq!(registers.len(), 1);
+//!
+//! let registers = WM8731::stereo_line_in(|c| c.volume().nearest_dB(3), |c| c.volume().nearest_dB(-3));
+//! assert_eq!(registers.len(), 2);
+//! ```
+
+use core::ops::Deref;
+
+use crate::Register;
+
+/// Bit that loads the settings into both channels
+pub(crate) const BOTH: u16 = 1 << 8;
+
+/// The address of the other channel of a line input or headphone out register
+pub(crate) fn other_channel(address: u8) -> Option<u8> {
+    match address {
+        0 => Some(1),
+        1 => Some(0),
+        2 => Some(3),
+        3 => Some(2),
+        _ => None,
+    }
+}
+
+/// One or two registers that set both channels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StereoWrite {
+    registers: [Register; 2],
+    len: usize,
+}
+
+impl StereoWrite {
+    /// Cheapest writes setting the channel at `left` to `left_value` and the one after it to
+    /// `right_value`
+    pub(crate) fn new(left: u8, left_value: u16, right_value: u16) -> Self {
+        let left_value = left_value & !BOTH;
+        let right_value = right_value & !BOTH;
+
+        if left_value == right_value {
+            StereoWrite {
+                registers: [
+                    Register {
+                        address: left,
+                        value: left_value | BOTH,
+                    },
+                    Register {
+                        address: left + 1,
+                        value: right_value,
+                    },
+                ],
+                len: 1,
+            }
+        } else {
+            StereoWrite {
+                registers: [
+                    Register {
+                        address: left,
+                        value: left_value,
+                    },
+                    Register {
+                        address: left + 1,
+                        value: right_value,
+                    },
+                ],
+                len: 2,
+            }
+        }
+    }
+}
+
+impl Deref for StereoWrite {
+    type Target = [Register];
+
+    fn deref(&self) -> &[Register] {
+        &self.registers[..self.len]
+    }
+}
+
+impl<'a> IntoIterator for &'a StereoWrite {
+    type Item = &'a Register;
+    type IntoIter = core::slice::Iter<'a, Register>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
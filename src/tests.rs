@@ -15,7 +15,8 @@ fn power_down() {
     });
 
     assert_eq!(result.address, 6);
-    assert_eq!(result.value, 0b0_0000_1101);
+    // mic, outputs, oscillator, CLKOUT and POWEROFF keep their powered-down reset defaults
+    assert_eq!(result.value, 0b0_1001_1111);
 }
 
 #[test]
@@ -76,7 +77,7 @@ fn possible_real_world() {
 
     // disable DAC mute, deemphasis for 48k
     let result = WM8731::digital_audio_path(|w| {
-        w.dac_mut();
+        w.dac_mute().disable();
         w.deemphasis().frequency_48();
     });
     assert_eq!(result.address, 0x5 /* digital audio path */);
@@ -102,7 +103,7 @@ fn possible_real_world() {
         w.usb_normal().normal();
     });
     assert_eq!(result.address, 0x8 /* sampling control */);
-    assert_eq!(result.value, 0b0_00_0000_00);
+    assert_eq!(result.value, 0b0_0000_0000);
 
     // set active
     let result = WM8731::active().active();
@@ -117,40 +118,41 @@ fn possible_real_world() {
 
 #[test]
 fn line_input_volume() {
-    // Make sure valid values result in the expected bitfields
+    // Make sure valid values result in the expected bitfields (LINVOL is bits 0-4; mute and
+    // both keep their reset defaults)
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(0));
-    assert_eq!(result.value, 0b1_0111);
+    assert_eq!(result.value & 0b1_1111, 0b1_0111);
 
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(12));
-    assert_eq!(result.value, 0b1_1111);
+    assert_eq!(result.value & 0b1_1111, 0b1_1111);
 
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(-34));
-    assert_eq!(result.value, 0b0_0000);
+    assert_eq!(result.value & 0b1_1111, 0b0_0000);
 
     // Make sure that in-between values get rounded
     // 1dB gets rounded up to 1.5dB
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(1));
-    assert_eq!(result.value, 0b1_1000);
+    assert_eq!(result.value & 0b1_1111, 0b1_1000);
 
     // 2dB gets rounded down to 1.5dB
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(2));
-    assert_eq!(result.value, 0b1_1000);
+    assert_eq!(result.value & 0b1_1111, 0b1_1000);
 
     // 3dB does not round at all
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(3));
-    assert_eq!(result.value, 0b1_1001);
+    assert_eq!(result.value & 0b1_1111, 0b1_1001);
 
     // -1dB gets rounded down to -1.5dB
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(-1));
-    assert_eq!(result.value, 0b1_0110);
+    assert_eq!(result.value & 0b1_1111, 0b1_0110);
 
     // -2dB gets rounded up to -1.5dB
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(-2));
-    assert_eq!(result.value, 0b1_0110);
+    assert_eq!(result.value & 0b1_1111, 0b1_0110);
 
     // -3dB does not round at all
     let result = WM8731::left_line_in(|w| w.volume().nearest_dB(-3));
-    assert_eq!(result.value, 0b1_0101);
+    assert_eq!(result.value & 0b1_1111, 0b1_0101);
 
     // Make sure that out-of-range values panic
     let result = catch_unwind(|| WM8731::left_line_in(|w| w.volume().nearest_dB(13)));
@@ -164,3 +166,2048 @@ fn line_input_volume() {
         let _ = WM8731::left_line_in(|w| w.volume().nearest_dB(gain));
     }
 }
+
+/// I²C bus that records every write and optionally refuses to acknowledge
+struct FakeI2c {
+    writes: std::vec::Vec<(u8, std::vec::Vec<u8>)>,
+    nack: bool,
+}
+
+impl FakeI2c {
+    fn new() -> Self {
+        FakeI2c {
+            writes: std::vec::Vec::new(),
+            nack: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct FakeI2cError(embedded_hal::i2c::ErrorKind);
+
+impl embedded_hal::i2c::Error for FakeI2cError {
+    fn kind(&self) -> embedded_hal::i2c::ErrorKind {
+        self.0
+    }
+}
+
+impl embedded_hal::i2c::ErrorType for FakeI2c {
+    type Error = FakeI2cError;
+}
+
+impl embedded_hal::i2c::I2c for FakeI2c {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [embedded_hal::i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if self.nack {
+            return Err(FakeI2cError(embedded_hal::i2c::ErrorKind::NoAcknowledge(
+                embedded_hal::i2c::NoAcknowledgeSource::Address,
+            )));
+        }
+
+        for operation in operations {
+            if let embedded_hal::i2c::Operation::Write(bytes) = operation {
+                self.writes.push((address, bytes.to_vec()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn driver_writes_registers() {
+    let mut codec = driver::Wm8731::new(FakeI2c::new());
+
+    codec.reset().unwrap();
+    codec
+        .left_line_in(|w| {
+            w.mute().disable();
+            w.volume().nearest_dB(0);
+        })
+        .unwrap();
+    codec
+        .power_down(|w| {
+            w.line_input().power_on();
+            w.output().power_on();
+        })
+        .unwrap();
+    let codec = codec.activate().map_err(|(_, e)| e).unwrap();
+
+    let i2c = codec.release();
+    assert_eq!(
+        i2c.writes,
+        [
+            (0x1a, std::vec![0b0001_1110, 0b0000_0000]),
+            (0x1a, std::vec![0b0000_0000, 0b0001_0111]),
+            (0x1a, std::vec![0b0000_1100, 0b1000_1110]),
+            (0x1a, std::vec![0b0001_0010, 0b0000_0001]),
+        ]
+    );
+}
+
+#[test]
+fn driver_writes_value_bit_8() {
+    let mut codec = driver::Wm8731::new_with_address(FakeI2c::new(), driver::ADDRESS_CSB_HIGH);
+
+    codec.right_headphone_out(|w| w.both().enable()).unwrap();
+
+    let i2c = codec.release();
+    assert_eq!(i2c.writes, [(0x1b, std::vec![0b0000_0111, 0b0111_1001])]);
+}
+
+#[test]
+fn driver_reports_nack() {
+    let mut i2c = FakeI2c::new();
+    i2c.nack = true;
+    let mut codec = driver::Wm8731::new(i2c);
+
+    assert_eq!(
+        codec.reset(),
+        Err(driver::Error::Nack(
+            embedded_hal::i2c::NoAcknowledgeSource::Address
+        ))
+    );
+}
+
+#[cfg(feature = "async")]
+impl embedded_hal_async::i2c::I2c for FakeI2c {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [embedded_hal::i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        embedded_hal::i2c::I2c::transaction(self, address, operations)
+    }
+}
+
+/// Poll a future that never waits to completion
+#[cfg(feature = "async")]
+fn block_on<F: core::future::Future>(future: F) -> F::Output {
+    let mut future = core::pin::pin!(future);
+    let mut context = core::task::Context::from_waker(core::task::Waker::noop());
+    loop {
+        if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_driver_writes_registers() {
+    let mut codec = async_driver::AsyncWm8731::new(FakeI2c::new());
+
+    let codec = block_on(async {
+        codec.reset().await.unwrap();
+        codec
+            .right_headphone_out(|w| w.both().enable())
+            .await
+            .unwrap();
+        codec.activate().await.map_err(|(_, e)| e).unwrap()
+    });
+
+    let i2c = codec.release();
+    assert_eq!(
+        i2c.writes,
+        [
+            (0x1a, std::vec![0b0001_1110, 0b0000_0000]),
+            (0x1a, std::vec![0b0000_0111, 0b0111_1001]),
+            (0x1a, std::vec![0b0001_0010, 0b0000_0001]),
+        ]
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_driver_reports_nack() {
+    let mut i2c = FakeI2c::new();
+    i2c.nack = true;
+    let codec = async_driver::AsyncWm8731::new(i2c);
+
+    assert_eq!(
+        block_on(codec.activate()).err().map(|(_, e)| e),
+        Some(driver::Error::Nack(
+            embedded_hal::i2c::NoAcknowledgeSource::Address
+        ))
+    );
+}
+
+/// SPI device that records the bytes clocked out in each chip-select transaction
+struct FakeSpi {
+    transactions: std::vec::Vec<std::vec::Vec<u8>>,
+}
+
+impl embedded_hal::spi::ErrorType for FakeSpi {
+    type Error = core::convert::Infallible;
+}
+
+impl embedded_hal::spi::SpiDevice for FakeSpi {
+    fn transaction(
+        &mut self,
+        operations: &mut [embedded_hal::spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        let mut bytes = std::vec::Vec::new();
+        for operation in operations {
+            if let embedded_hal::spi::Operation::Write(data) = operation {
+                bytes.extend_from_slice(data);
+            }
+        }
+        self.transactions.push(bytes);
+
+        Ok(())
+    }
+}
+
+#[test]
+fn spi_driver_writes_one_word_per_register() {
+    let mut codec = driver::Wm8731::new_spi(FakeSpi {
+        transactions: std::vec::Vec::new(),
+    });
+
+    codec.reset().unwrap();
+    codec.left_headphone_out(|w| w.both().enable()).unwrap();
+    let codec = codec
+        .activate()
+        .map_err(|(_, e)| e)
+        .unwrap()
+        .deactivate()
+        .map_err(|(_, e)| e)
+        .unwrap();
+
+    let spi = codec.release();
+    assert_eq!(
+        spi.transactions,
+        [
+            std::vec![0b0001_1110, 0b0000_0000],
+            std::vec![0b0000_0101, 0b0111_1001],
+            std::vec![0b0001_0010, 0b0000_0001],
+            std::vec![0b0001_0010, 0b0000_0000],
+        ]
+    );
+}
+
+#[test]
+fn register_encoding_round_trips() {
+    for &address in Register::ADDRESSES.iter() {
+        for value in 0..=Register::MAX_VALUE {
+            let register = Register { address, value };
+
+            let word = register.to_u16().unwrap();
+            assert_eq!(word >> 9, address as u16);
+            assert_eq!(word & 0x1ff, value);
+
+            let bytes = register.to_bytes().unwrap();
+            assert_eq!(bytes[0] >> 1, address);
+            assert_eq!(bytes[0] & 1, (value >> 8) as u8);
+            assert_eq!(bytes[1], value as u8);
+
+            assert_eq!(Register::from_u16(word), Ok(register));
+            assert_eq!(Register::from_bytes(bytes), Ok(register));
+        }
+    }
+}
+
+#[test]
+fn register_encoding_rejects_out_of_range() {
+    for address in 0..=u8::MAX {
+        let register = Register { address, value: 0 };
+        if Register::ADDRESSES.contains(&address) {
+            assert!(register.to_u16().is_ok());
+        } else {
+            assert_eq!(
+                register.to_u16(),
+                Err(RegisterError::InvalidAddress(address))
+            );
+            assert_eq!(
+                register.to_bytes(),
+                Err(RegisterError::InvalidAddress(address))
+            );
+        }
+    }
+
+    let register = Register {
+        address: 4,
+        value: 0x200,
+    };
+    assert_eq!(register.to_u16(), Err(RegisterError::InvalidValue(0x200)));
+    assert_eq!(register.to_bytes(), Err(RegisterError::InvalidValue(0x200)));
+
+    assert_eq!(
+        Register::from_u16(0b0001010_000000000),
+        Err(RegisterError::InvalidAddress(10))
+    );
+    assert_eq!(
+        Register::from_bytes([0xfe, 0x00]),
+        Err(RegisterError::InvalidAddress(127))
+    );
+}
+
+#[test]
+fn driver_rejects_invalid_registers() {
+    let mut codec = driver::Wm8731::new(FakeI2c::new());
+
+    assert_eq!(
+        codec.write(Register {
+            address: 2,
+            value: 0x3ff
+        }),
+        Err(driver::Error::Register(RegisterError::InvalidValue(0x3ff)))
+    );
+
+    assert!(codec.release().writes.is_empty());
+}
+
+#[test]
+fn shadow_starts_at_reset_defaults() {
+    let shadow = shadow::ShadowRegisters::new();
+
+    for register in shadow.registers().iter() {
+        let default = match register.address {
+            0 => WM8731::left_line_in(|_| {}),
+            1 => WM8731::right_line_in(|_| {}),
+            2 => WM8731::left_headphone_out(|_| {}),
+            3 => WM8731::right_headphone_out(|_| {}),
+            4 => WM8731::analog_audio_path(|_| {}),
+            5 => WM8731::digital_audio_path(|_| {}),
+            6 => WM8731::power_down(|_| {}),
+            7 => WM8731::digital_audio_interface_format(|_| {}),
+            8 => WM8731::sampling(|_| {}),
+            9 => WM8731::active().inactive(),
+            _ => unreachable!(),
+        };
+        assert_eq!(*register, default);
+    }
+
+    assert_eq!(shadow.get(15), None);
+    assert!(!shadow.is_active());
+}
+
+#[test]
+fn shadow_read_modify_write_keeps_other_fields() {
+    let mut shadow = shadow::ShadowRegisters::new();
+
+    let result = shadow.power_down(|w| {
+        w.line_input().power_on();
+        w.adc().power_on();
+        w.dac().power_on();
+        w.output().power_off();
+    });
+    assert_eq!(result.address, 6);
+    assert_eq!(result.value, 0b0_1001_0010);
+
+    // Only the outputs change; everything else stays as last written
+    let result = shadow.power_down(|w| w.output().power_on());
+    assert_eq!(result.address, 6);
+    assert_eq!(result.value, 0b0_1000_0010);
+    assert_eq!(shadow.get(6), Some(0b0_1000_0010));
+
+    shadow.record(WM8731::digital_audio_path(|w| {
+        w.dac_mute().disable();
+        w.deemphasis().frequency_441();
+    }));
+    let result = shadow.digital_audio_path(|w| w.dac_mute().enable());
+    assert_eq!(result.value, 0b0_0000_1100);
+
+    assert_eq!(shadow.activate(), WM8731::active().active());
+    assert!(shadow.is_active());
+}
+
+#[test]
+fn shadow_reset_reseeds_defaults() {
+    let mut shadow = shadow::ShadowRegisters::new();
+
+    shadow.left_line_in(|w| w.mute().disable());
+    shadow.sampling(|w| w.sample_rate().adc_96().dac_96());
+    shadow.activate();
+
+    assert_eq!(shadow.reset(), WM8731::reset());
+    assert_eq!(shadow, shadow::ShadowRegisters::new());
+
+    shadow.sampling(|w| w.usb_normal().usb());
+    shadow.record(Register {
+        address: 15,
+        value: 0,
+    });
+    assert_eq!(shadow, shadow::ShadowRegisters::new());
+}
+
+#[test]
+fn headphone_output_volume() {
+    let result = WM8731::left_headphone_out(|w| w.volume().dB(0).unwrap());
+    assert_eq!(result.value, 0b0_0111_1001);
+
+    let result = WM8731::left_headphone_out(|w| w.volume().dB(6).unwrap());
+    assert_eq!(result.value, 0b0_0111_1111);
+
+    let result = WM8731::left_headphone_out(|w| w.volume().dB(-73).unwrap());
+    assert_eq!(result.value, 0b0_0011_0000);
+
+    // Volume does not disturb zero cross or both
+    let result = WM8731::right_headphone_out(|w| {
+        w.both().enable();
+        w.zero_cross_detect().enable();
+        w.volume().dB(-10).unwrap();
+    });
+    assert_eq!(result.value, 0b1_1110_1111);
+
+    // Every step is exactly 1dB
+    for gain in -73..=6 {
+        let result = WM8731::left_headphone_out(|w| w.volume().dB(gain).unwrap());
+        assert_eq!(result.value as i16, 0b111_1001 + gain);
+    }
+
+    // Out-of-range values are rejected and leave the volume alone
+    let result = WM8731::left_headphone_out(|w| {
+        assert_eq!(w.volume().dB(7), Err(GainError::OutOfRange));
+        assert_eq!(w.volume().dB(-74), Err(GainError::OutOfRange));
+    });
+    assert_eq!(result.value, 0b0_0111_1001);
+
+    let result = WM8731::left_headphone_out(|w| w.volume().mute());
+    assert_eq!(result.value, 0b0_0000_0000);
+}
+
+#[test]
+fn sidetone_attenuation() {
+    let result = WM8731::analog_audio_path(|w| w.sidetone_attenuation().minus_6());
+    assert_eq!(result.address, 4);
+    assert_eq!(result.value, 0b0_0000_1010);
+
+    let result = WM8731::analog_audio_path(|w| w.sidetone_attenuation().minus_9());
+    assert_eq!(result.value, 0b0_0100_1010);
+
+    let result = WM8731::analog_audio_path(|w| w.sidetone_attenuation().minus_12());
+    assert_eq!(result.value, 0b0_1000_1010);
+
+    let result = WM8731::analog_audio_path(|w| w.sidetone_attenuation().minus_15());
+    assert_eq!(result.value, 0b0_1100_1010);
+
+    // Attenuation does not disturb the side tone switch
+    let result = WM8731::analog_audio_path(|w| {
+        w.sidetone().enable();
+        w.sidetone_attenuation().minus_15();
+        w.sidetone_attenuation().minus_9();
+    });
+    assert_eq!(result.value, 0b0_0110_1010);
+}
+
+#[test]
+fn sidetone_attenuation_nearest() {
+    let expected = [
+        (0, 0b00),
+        (-6, 0b00),
+        (-7, 0b00),
+        (-8, 0b01),
+        (-9, 0b01),
+        (-10, 0b01),
+        (-11, 0b10),
+        (-12, 0b10),
+        (-13, 0b10),
+        (-14, 0b11),
+        (-15, 0b11),
+        (-40, 0b11),
+    ];
+
+    for &(gain, bits) in expected.iter() {
+        let result = WM8731::analog_audio_path(|w| w.sidetone_attenuation().nearest_dB(gain));
+        assert_eq!(result.value >> 6, bits, "{}dB", gain);
+    }
+}
+
+#[test]
+fn line_input_volume_fallible() {
+    // Valid values behave like the panicking versions
+    for gain in -35..=12 {
+        let expected = WM8731::left_line_in(|w| w.volume().nearest_dB(gain));
+        let result = WM8731::left_line_in(|w| w.volume().try_nearest_dB(gain).unwrap());
+        assert_eq!(result, expected);
+    }
+
+    let result = WM8731::left_line_in(|w| w.volume().try_half_dB_steps(3).unwrap());
+    assert_eq!(result.value & 0b1_1111, 0b1_1000);
+
+    // Out-of-range and off-grid values are reported and leave the volume alone
+    let result = WM8731::left_line_in(|w| {
+        assert_eq!(w.volume().try_nearest_dB(13), Err(GainError::OutOfRange));
+        assert_eq!(w.volume().try_nearest_dB(-36), Err(GainError::OutOfRange));
+        assert_eq!(
+            w.volume().try_nearest_dB(i16::MIN),
+            Err(GainError::OutOfRange)
+        );
+        assert_eq!(
+            w.volume().try_nearest_dB(i16::MAX),
+            Err(GainError::OutOfRange)
+        );
+        assert_eq!(w.volume().try_half_dB_steps(25), Err(GainError::OutOfRange));
+        assert_eq!(
+            w.volume().try_half_dB_steps(-70),
+            Err(GainError::OutOfRange)
+        );
+        assert_eq!(w.volume().try_half_dB_steps(1), Err(GainError::OffGrid));
+        assert_eq!(w.volume().try_half_dB_steps(-2), Err(GainError::OffGrid));
+    });
+    assert_eq!(result.value, 0b0_1001_0111);
+}
+
+#[test]
+fn volume_saturating() {
+    let result = WM8731::left_line_in(|w| w.volume().saturating_nearest_dB(1));
+    assert_eq!(result.value & 0b1_1111, 0b1_1000);
+
+    let result = WM8731::left_line_in(|w| w.volume().saturating_nearest_dB(40));
+    assert_eq!(result.value & 0b1_1111, 0b1_1111);
+
+    let result = WM8731::left_line_in(|w| w.volume().saturating_nearest_dB(i16::MIN));
+    assert_eq!(result.value & 0b1_1111, 0b0_0000);
+
+    let result = WM8731::left_headphone_out(|w| w.volume().saturating_nearest_dB(-3));
+    assert_eq!(result.value, 0b111_1001 - 3);
+
+    let result = WM8731::left_headphone_out(|w| w.volume().saturating_nearest_dB(20));
+    assert_eq!(result.value, 0b111_1111);
+
+    let result = WM8731::left_headphone_out(|w| w.volume().saturating_nearest_dB(i16::MIN));
+    assert_eq!(result.value, 0b011_0000);
+}
+
+#[test]
+fn rate_solver_normal_mode() {
+    use rate_solver::{solve, SampleRate::*};
+
+    let sampling = |mclk, adc, dac| WM8731::sampling(|s| *s = solve(mclk, adc, dac).unwrap());
+
+    assert_eq!(sampling(12_288_000, Hz48000, Hz48000).value, 0b0_0000_0000);
+    assert_eq!(sampling(12_288_000, Hz48000, Hz8000).value, 0b0_0000_0100);
+    assert_eq!(sampling(12_288_000, Hz96000, Hz96000).value, 0b0_0001_1100);
+    assert_eq!(sampling(18_432_000, Hz32000, Hz32000).value, 0b0_0001_1010);
+    assert_eq!(sampling(11_289_600, Hz44100, Hz44100).value, 0b0_0010_0000);
+    assert_eq!(sampling(16_934_400, Hz8018, Hz44100).value, 0b0_0010_1010);
+    assert_eq!(sampling(11_289_600, Hz88200, Hz88200).value, 0b0_0011_1100);
+
+    // Twice the MCLK enables the core clock divider
+    assert_eq!(sampling(24_576_000, Hz48000, Hz48000).value, 0b0_0100_0000);
+    assert_eq!(sampling(33_868_800, Hz44100, Hz44100).value, 0b0_0110_0010);
+}
+
+#[test]
+fn rate_solver_usb_mode() {
+    use rate_solver::{solve, SampleRate::*};
+
+    let sampling = |mclk, adc, dac| WM8731::sampling(|s| *s = solve(mclk, adc, dac).unwrap());
+
+    assert_eq!(sampling(12_000_000, Hz48000, Hz48000).value, 0b0_0000_0001);
+    assert_eq!(sampling(12_000_000, Hz44100, Hz44100).value, 0b0_0010_0011);
+    assert_eq!(sampling(12_000_000, Hz8021, Hz8021).value, 0b0_0010_1111);
+    assert_eq!(sampling(12_000_000, Hz96000, Hz96000).value, 0b0_0001_1101);
+    assert_eq!(sampling(12_000_000, Hz88200, Hz88200).value, 0b0_0011_1111);
+    assert_eq!(sampling(24_000_000, Hz32000, Hz32000).value, 0b0_0101_1001);
+}
+
+#[test]
+fn rate_solver_errors() {
+    use rate_solver::{solve, SampleRate::*};
+
+    assert_eq!(
+        solve(12_345_678, Hz48000, Hz48000).err(),
+        Some(RateError::UnsupportedMclk(12_345_678))
+    );
+
+    // 44.1kHz cannot be derived from a 48kHz-family MCLK
+    let achievable = match solve(12_288_000, Hz44100, Hz44100) {
+        Err(RateError::Unachievable(achievable)) => achievable,
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    };
+    assert_eq!(
+        achievable.iter().collect::<std::vec::Vec<_>>(),
+        [
+            (Hz48000, Hz48000),
+            (Hz48000, Hz8000),
+            (Hz8000, Hz48000),
+            (Hz8000, Hz8000),
+            (Hz32000, Hz32000),
+            (Hz96000, Hz96000),
+        ]
+    );
+
+    // 8.018kHz is only available in normal mode
+    assert!(matches!(
+        solve(12_000_000, Hz8018, Hz8018),
+        Err(RateError::Unachievable(_))
+    ));
+    // and mixed rates must be one of the listed pairs
+    assert!(matches!(
+        solve(11_289_600, Hz88200, Hz44100),
+        Err(RateError::Unachievable(_))
+    ));
+}
+
+#[test]
+fn usb_sampling_rate() {
+    let expected = [
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_48().dac_48()),
+            0b0_0000_0001,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_48().dac_8()),
+            0b0_0000_0101,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_8().dac_48()),
+            0b0_0000_1001,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_8().dac_8()),
+            0b0_0000_1101,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_32().dac_32()),
+            0b0_0001_1001,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_96().dac_96()),
+            0b0_0001_1101,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_441().dac_441()),
+            0b0_0010_0011,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_441().dac_8021()),
+            0b0_0010_0111,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_8021().dac_441()),
+            0b0_0010_1011,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_8021().dac_8021()),
+            0b0_0010_1111,
+        ),
+        (
+            WM8731::sampling(|w| w.sample_rate().usb().adc_882().dac_882()),
+            0b0_0011_1111,
+        ),
+    ];
+
+    for (result, value) in expected.iter() {
+        assert_eq!(result.address, 8);
+        assert_eq!(result.value, *value);
+    }
+
+    // BOSR is cleared again when switching back to a 250fs rate, and the dividers are untouched
+    let result = WM8731::sampling(|w| {
+        w.core_clock_divider_select().divided_by_two();
+        w.sample_rate().usb().adc_441().dac_441();
+        w.sample_rate().usb().adc_32().dac_32();
+    });
+    assert_eq!(result.value, 0b0_0101_1001);
+}
+
+#[test]
+fn power_up_sequence() {
+    use sequence::Step;
+
+    let mut power = power_down::PowerDown::new();
+    power.line_input().power_on();
+    power.adc().power_on();
+    power.dac().power_on();
+    power.output().power_on();
+    power.power_off().power_on();
+
+    let configuration = [
+        WM8731::analog_audio_path(|w| w.dac_select().select()),
+        WM8731::digital_audio_path(|w| w.dac_mute().disable()),
+    ];
+
+    let steps: std::vec::Vec<_> = sequence::power_up(power, &configuration, 250).collect();
+    assert_eq!(
+        steps,
+        [
+            Step::Write(Register {
+                address: 6,
+                value: 0b0_0001_0010
+            }),
+            Step::Write(configuration[0]),
+            Step::Write(configuration[1]),
+            Step::Write(WM8731::active().active()),
+            Step::Delay { ms: 250 },
+            Step::Write(Register {
+                address: 6,
+                value: 0b0_0000_0010
+            }),
+        ]
+    );
+}
+
+#[test]
+fn power_down_and_standby_sequences() {
+    use sequence::Step;
+
+    let mut power = power_down::PowerDown::new();
+    power.dac().power_on();
+    power.output().power_on();
+    power.power_off().power_on();
+    // The DAC is soft muted without touching the other settings
+    let mut path = digital_audio_path::DigitalAudioPath::new();
+    path.dac_mute().disable();
+    path.deemphasis().frequency_48();
+
+    assert_eq!(
+        sequence::power_down(power, path),
+        [
+            Step::Write(Register {
+                address: 5,
+                value: 0b0_0000_1110
+            }),
+            Step::Write(Register {
+                address: 6,
+                value: 0b0_0001_0111
+            }),
+            Step::Write(WM8731::active().inactive()),
+            Step::Write(Register {
+                address: 6,
+                value: 0b0_1111_1111
+            }),
+        ]
+    );
+
+    assert_eq!(
+        sequence::standby(power, path),
+        [
+            Step::Write(Register {
+                address: 5,
+                value: 0b0_0000_1110
+            }),
+            Step::Write(Register {
+                address: 6,
+                value: 0b0_0001_0111
+            }),
+            Step::Write(WM8731::active().inactive()),
+            Step::Write(Register {
+                address: 6,
+                value: 0b0_0111_1111
+            }),
+        ]
+    );
+}
+
+/// Delay that records how long it was asked to wait instead of waiting
+struct FakeDelay {
+    ns: u64,
+}
+
+impl embedded_hal::delay::DelayNs for FakeDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.ns += u64::from(ns);
+    }
+}
+
+#[test]
+fn driver_runs_sequences() {
+    let codec = driver::Wm8731::new(FakeI2c::new());
+    let mut delay = FakeDelay { ns: 0 };
+
+    let power = power_down::PowerDown::new();
+    let codec = codec
+        .run_power_up(power, &[], 100, &mut delay)
+        .map_err(|(_, e)| e)
+        .unwrap();
+    let path = digital_audio_path::DigitalAudioPath::new();
+    let codec = codec
+        .run_power_down(power, path, &mut delay)
+        .map_err(|(_, e)| e)
+        .unwrap();
+
+    assert_eq!(delay.ns, 100_000_000);
+    assert_eq!(codec.release().writes.len(), 7);
+}
+
+#[test]
+fn config_defaults_match_reset() {
+    let config = config::CodecConfig::new();
+
+    assert_eq!(
+        config.registers().unwrap(),
+        shadow::ShadowRegisters::new().registers()
+    );
+}
+
+#[test]
+fn config_renders_registers() {
+    use config::*;
+
+    let mut config = CodecConfig::new();
+    config.left_line_in.mute = false;
+    config.left_line_in.gain_half_db = 3;
+    config.right_headphone_out.volume_db = None;
+    config.right_headphone_out.both = true;
+    config.analog_audio_path.bypass = false;
+    config.analog_audio_path.dac_select = true;
+    config.analog_audio_path.sidetone_attenuation = SidetoneLevel::Minus12dB;
+    config.digital_audio_path.dac_mute = false;
+    config.digital_audio_path.deemphasis = Deemphasis::Khz48;
+    config.power = PowerConfig {
+        line_input: true,
+        mic: false,
+        adc: true,
+        dac: true,
+        output: true,
+        oscillator: false,
+        clock_output: false,
+        chip: true,
+    };
+    config.format.format = AudioFormat::LeftJustified;
+    config.format.master = true;
+    config.sampling = SamplingConfig {
+        mclk_hz: 11_289_600,
+        adc: rate_solver::SampleRate::Hz44100,
+        dac: rate_solver::SampleRate::Hz44100,
+        clock_out_divide_by_two: true,
+    };
+    config.active = true;
+
+    let values: std::vec::Vec<_> = config
+        .registers()
+        .unwrap()
+        .iter()
+        .map(|r| (r.address, r.value))
+        .collect();
+    assert_eq!(
+        values,
+        [
+            (0, 0b0_0001_1000),
+            (1, 0b0_1001_0111),
+            (2, 0b0_0111_1001),
+            (3, 0b1_0000_0000),
+            (4, 0b0_1001_0010),
+            (5, 0b0_0000_0110),
+            (6, 0b0_0110_0010),
+            (7, 0b0_0100_1001),
+            (8, 0b0_1010_0000),
+            (9, 0b0_0000_0001),
+        ]
+    );
+
+    let sequence = config.init_sequence(250).unwrap();
+    let registers: std::vec::Vec<_> = sequence
+        .iter()
+        .filter_map(|step| match step {
+            sequence::Step::Write(register) => Some(*register),
+            sequence::Step::Delay { .. } => None,
+        })
+        .collect();
+    let addresses: std::vec::Vec<_> = registers.iter().map(|r| r.address).collect();
+    assert_eq!(addresses, [15, 6, 0, 1, 2, 3, 4, 5, 7, 8, 9, 6]);
+    assert_eq!(registers[1].value, 0b0_0111_0010);
+    assert_eq!(registers[11].value, 0b0_0110_0010);
+    assert_eq!(sequence[11], sequence::Step::Delay { ms: 250 });
+    // An inactive configuration leaves the interface inactive
+    assert_eq!(registers[10], WM8731::active().active());
+    config.active = false;
+    let sequence = config.init_sequence(250).unwrap();
+    assert_eq!(
+        sequence[10],
+        sequence::Step::Write(WM8731::active().inactive())
+    );
+}
+
+#[test]
+fn config_diff() {
+    let before = config::CodecConfig::new();
+    assert_eq!(before.diff(&before).unwrap().count(), 0);
+
+    let mut after = before;
+    after.right_headphone_out.volume_db = Some(-10);
+    after.power.dac = true;
+    let changed: std::vec::Vec<_> = after.diff(&before).unwrap().collect();
+    assert_eq!(
+        changed,
+        [
+            WM8731::right_headphone_out(|w| w.volume().dB(-10).unwrap()),
+            WM8731::power_down(|w| w.dac().power_on()),
+        ]
+    );
+}
+
+#[test]
+fn config_diff_keeps_the_other_channel() {
+    let mut before = config::CodecConfig::new();
+    before.left_line_in.both = true;
+    before.right_line_in.gain_half_db = 6;
+    let mut sim = simulator::Simulator::new();
+    for step in before.init_sequence(0).unwrap().iter() {
+        if let sequence::Step::Write(register) = step {
+            sim.write(*register);
+        }
+    }
+    // The init sequence writes R1 after R0, so the right channel keeps its own gain
+    assert_eq!(sim.right_line_in().gain_half_db, 6);
+
+    let mut after = before;
+    after.left_line_in.mute = false;
+    let changed: std::vec::Vec<_> = after.diff(&before).unwrap().collect();
+    assert_eq!(changed.len(), 2);
+    for register in changed {
+        sim.write(register);
+    }
+    assert!(!sim.left_line_in().mute);
+    assert_eq!(sim.right_line_in().gain_half_db, 6);
+
+    // A right channel write with the both bit set is followed by the left channel
+    let mut before = config::CodecConfig::new();
+    before.left_headphone_out.volume_db = Some(-6);
+    let mut after = before;
+    after.right_headphone_out.both = true;
+    let changed: std::vec::Vec<_> = after.diff(&before).unwrap().collect();
+    assert_eq!(changed[0].address, 3);
+    assert_eq!(changed[1].address, 2);
+}
+
+#[test]
+fn config_diff_deactivates_for_format_and_sampling() {
+    let mut before = config::CodecConfig::new();
+    before.active = true;
+    let mut after = before;
+    after.format.word_length = config::WordLength::Bits16;
+    after.sampling.adc = rate_solver::SampleRate::Hz8000;
+    after.sampling.dac = rate_solver::SampleRate::Hz8000;
+
+    let changed: std::vec::Vec<_> = after.diff(&before).unwrap().collect();
+    let addresses: std::vec::Vec<_> = changed.iter().map(|r| r.address).collect();
+    assert_eq!(addresses, [9, 7, 8, 9]);
+    assert_eq!(changed[0], WM8731::active().inactive());
+    assert_eq!(changed[3], WM8731::active().active());
+
+    // Deactivating along the way needs no second write
+    after.active = false;
+    let changed: std::vec::Vec<_> = after.diff(&before).unwrap().collect();
+    assert_eq!(changed.len(), 3);
+    assert_eq!(changed[0], WM8731::active().inactive());
+
+    // An inactive codec is changed in address order
+    let changed: std::vec::Vec<_> = after.diff(&after).unwrap().collect();
+    assert!(changed.is_empty());
+    before.active = false;
+    after.active = true;
+    let addresses: std::vec::Vec<_> = after.diff(&before).unwrap().map(|r| r.address).collect();
+    assert_eq!(addresses, [7, 8, 9]);
+}
+
+#[test]
+fn config_errors() {
+    let mut config = config::CodecConfig::new();
+    config.left_line_in.gain_half_db = 1;
+    assert_eq!(
+        config.registers(),
+        Err(ConfigError::Gain(GainError::OffGrid))
+    );
+
+    let mut config = config::CodecConfig::new();
+    config.left_headphone_out.volume_db = Some(7);
+    assert_eq!(
+        config.init_sequence(0),
+        Err(ConfigError::Gain(GainError::OutOfRange))
+    );
+
+    let mut config = config::CodecConfig::new();
+    config.sampling.mclk_hz = 10_000_000;
+    assert!(config.diff(&config::CodecConfig::new()).is_err());
+}
+
+#[test]
+fn decode_round_trips_config() {
+    use decode::{decode, Decoded};
+
+    let mut config = config::CodecConfig::new();
+    config.left_line_in.gain_half_db = -69;
+    config.right_line_in.gain_half_db = 24;
+    config.right_line_in.both = true;
+    config.left_headphone_out.volume_db = Some(-73);
+    config.right_headphone_out.volume_db = None;
+    config.right_headphone_out.zero_cross_detect = true;
+    config.analog_audio_path.input = config::Input::Mic;
+    config.analog_audio_path.sidetone_attenuation = config::SidetoneLevel::Minus15dB;
+    config.digital_audio_path.deemphasis = config::Deemphasis::Khz441;
+    config.digital_audio_path.store_dc_offset = true;
+    config.power.mic = true;
+    config.format.format = config::AudioFormat::Dsp;
+    config.format.word_length = config::WordLength::Bits20;
+    config.format.left_right_swap = true;
+    config.active = true;
+
+    let registers = config.registers().unwrap();
+    assert_eq!(
+        decode(registers[0]),
+        Ok(Decoded::LeftLineIn(config.left_line_in))
+    );
+    assert_eq!(
+        decode(registers[1]),
+        Ok(Decoded::RightLineIn(config.right_line_in))
+    );
+    assert_eq!(
+        decode(registers[2]),
+        Ok(Decoded::LeftHeadphoneOut(config.left_headphone_out))
+    );
+    assert_eq!(
+        decode(registers[3]),
+        Ok(Decoded::RightHeadphoneOut(config.right_headphone_out))
+    );
+    assert_eq!(
+        decode(registers[4]),
+        Ok(Decoded::AnalogAudioPath(config.analog_audio_path))
+    );
+    assert_eq!(
+        decode(registers[5]),
+        Ok(Decoded::DigitalAudioPath(config.digital_audio_path))
+    );
+    assert_eq!(decode(registers[6]), Ok(Decoded::PowerDown(config.power)));
+    assert_eq!(
+        decode(registers[7]),
+        Ok(Decoded::DigitalAudioInterfaceFormat(config.format))
+    );
+    assert_eq!(decode(registers[9]), Ok(Decoded::Active(true)));
+    assert_eq!(decode(WM8731::reset()), Ok(Decoded::Reset));
+}
+
+#[test]
+fn decode_sampling() {
+    use decode::{decode_u16, Decoded, SamplingSettings};
+    use rate_solver::SampleRate::*;
+
+    let word = WM8731::sampling(|w| {
+        w.sample_rate().usb().adc_8021().dac_441();
+        w.clock_out_divider_select().divided_by_two();
+    })
+    .to_u16()
+    .unwrap();
+    assert_eq!(
+        decode_u16(word),
+        Ok(Decoded::Sampling(SamplingSettings {
+            usb: true,
+            bosr: true,
+            adc: Hz8021,
+            dac: Hz44100,
+            core_clock_divide_by_two: false,
+            clock_out_divide_by_two: true,
+        }))
+    );
+
+    let register = WM8731::sampling(|w| {
+        w.sample_rate().adc_8018().dac_441();
+        w.core_clock_divider_select().divided_by_two();
+    });
+    assert_eq!(
+        decode::decode(register),
+        Ok(Decoded::Sampling(SamplingSettings {
+            usb: false,
+            bosr: false,
+            adc: Hz8018,
+            dac: Hz44100,
+            core_clock_divide_by_two: true,
+            clock_out_divide_by_two: false,
+        }))
+    );
+}
+
+#[test]
+fn decode_errors() {
+    use decode::decode;
+
+    for &code in [0b0100, 0b0101, 0b1100, 0b1101, 0b1110].iter() {
+        let register = Register {
+            address: 8,
+            value: code << 2,
+        };
+        assert_eq!(
+            decode(register),
+            Err(DecodeError::ReservedSampleRate(code as u8))
+        );
+    }
+
+    let register = Register {
+        address: 0,
+        value: 0b0_0010_0000,
+    };
+    assert_eq!(
+        decode(register),
+        Err(DecodeError::ReservedBits {
+            address: 0,
+            value: 0b0_0010_0000
+        })
+    );
+
+    let register = Register {
+        address: 9,
+        value: 0b0_0000_0011,
+    };
+    assert!(matches!(
+        decode(register),
+        Err(DecodeError::ReservedBits { .. })
+    ));
+
+    let register = Register {
+        address: 5,
+        value: 0b1_0000_0000,
+    };
+    assert!(matches!(
+        decode(register),
+        Err(DecodeError::ReservedBits { .. })
+    ));
+
+    let register = Register {
+        address: 12,
+        value: 0,
+    };
+    assert_eq!(
+        decode(register),
+        Err(DecodeError::Register(RegisterError::InvalidAddress(12)))
+    );
+
+    // Every headphone volume below 0b011_0000 mutes
+    for value in 0..0b011_0000 {
+        let register = Register { address: 2, value };
+        assert!(matches!(
+            decode(register),
+            Ok(decode::Decoded::LeftHeadphoneOut(
+                config::HeadphoneOutConfig {
+                    volume_db: None,
+                    ..
+                }
+            ))
+        ));
+    }
+}
+
+#[test]
+fn register_debug_output() {
+    extern crate alloc;
+    use alloc::format;
+
+    let mut pd = power_down::PowerDown::new();
+    pd.line_input().power_on();
+    pd.adc().power_on();
+    pd.power_off().power_on();
+    assert_eq!(
+        format!("{:?}", pd),
+        "PowerDown { line_input: on, mic: off, adc: on, dac: off, output: off, \
+         oscillator: on, clock_output: on, chip: on }"
+    );
+    assert_eq!(format!("{}", pd), format!("{:?}", pd));
+
+    let mut li = line_in::LineIn::new();
+    li.volume().try_half_dB_steps(-69).unwrap();
+    assert_eq!(
+        format!("{:?}", li),
+        "LineIn { volume: -34.5dB, mute: enabled, both: disabled }"
+    );
+
+    let mut ho = headphone_out::HeadphoneOut::new();
+    assert_eq!(
+        format!("{:?}", ho),
+        "HeadphoneOut { volume: 0dB, zero_cross_detect: disabled, both: disabled }"
+    );
+    ho.volume().mute();
+    assert_eq!(
+        format!("{:?}", ho),
+        "HeadphoneOut { volume: muted, zero_cross_detect: disabled, both: disabled }"
+    );
+
+    let aap = analog_audio_path::AnalogAudioPath::new();
+    assert_eq!(
+        format!("{:?}", aap),
+        "AnalogAudioPath { mic_boost: disabled, mute_mic: enabled, input_select: line input, \
+         bypass: enabled, dac_select: deselected, sidetone: disabled, \
+         sidetone_attenuation: -6dB }"
+    );
+
+    let mut dap = digital_audio_path::DigitalAudioPath::new();
+    dap.deemphasis().frequency_441();
+    assert_eq!(
+        format!("{:?}", dap),
+        "DigitalAudioPath { adc_hpf: enabled, deemphasis: 44.1kHz, dac_mute: enabled, \
+         hpor: clear }"
+    );
+
+    let mut daif = digital_audio_interface_format::DigitalAudioInterfaceFormat::new();
+    daif.master_slave().master();
+    daif.bit_clock_invert().invert();
+    assert_eq!(
+        format!("{:?}", daif),
+        "DigitalAudioInterfaceFormat { format: I²S, bit_length: 24 bits, \
+         left_right_phase: right channel when DACLRC low, left_right_dac_clock_swap: disabled, \
+         master_slave: master, bit_clock_invert: inverted }"
+    );
+
+    let mut s = sampling::Sampling::new();
+    s.sample_rate().usb().adc_441().dac_8021();
+    s.core_clock_divider_select().divided_by_two();
+    assert_eq!(
+        format!("{:?}", s),
+        "Sampling { usb_normal: USB, base_oversampling_rate: 272fs, \
+         sample_rate: ADC 44.1kHz, DAC 8.021kHz, core_clock_divider_select: MCLK/2, \
+         clock_out_divider_select: MCLK }"
+    );
+
+    let register = Register {
+        address: 8,
+        value: 0b0_0001_0000,
+    };
+    assert_eq!(
+        format!("{}", register),
+        "R8 sampling: Sampling { usb_normal: normal, base_oversampling_rate: 256fs, \
+         sample_rate: reserved (0b0100), core_clock_divider_select: MCLK, \
+         clock_out_divider_select: MCLK }"
+    );
+    assert_eq!(format!("{}", WM8731::active().active()), "R9 active");
+    assert_eq!(format!("{}", WM8731::reset()), "R15 reset");
+}
+
+#[cfg(feature = "defmt")]
+#[test]
+fn defmt_format_impls() {
+    fn assert_format<T: defmt::Format>() {}
+
+    assert_format::<Register>();
+    assert_format::<LineIn>();
+    assert_format::<HeadphoneOut>();
+    assert_format::<AnalogAudioPath>();
+    assert_format::<DigitalAudioPath>();
+    assert_format::<PowerDown>();
+    assert_format::<DigitalAudioInterfaceFormat>();
+    assert_format::<Sampling>();
+    assert_format::<driver::Error<embedded_hal::i2c::ErrorKind>>();
+    assert_format::<driver::Error<simulator::Error>>();
+    assert_format::<ConfigError>();
+    assert_format::<DecodeError>();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn codec_config_serde() {
+    use config::{AudioFormat, CodecConfig};
+
+    let mut config = CodecConfig::new();
+    config.format.format = AudioFormat::LeftJustified;
+    config.left_line_in.set_gain_db(-1.5).unwrap();
+    assert_eq!(config.left_line_in.gain_half_db, -3);
+    assert_eq!(config.left_line_in.gain_db(), -1.5);
+
+    let text = toml::to_string(&config).unwrap();
+    assert!(text.contains("format = \"left_justified\""));
+    assert!(text.contains("gain_db = -1.5"));
+    assert!(text.contains("adc = \"hz48000\""));
+    assert_eq!(toml::from_str::<CodecConfig>(&text).unwrap(), config);
+
+    let mut buffer = [0u8; 128];
+    let bytes = postcard::to_slice(&config, &mut buffer).unwrap();
+    assert_eq!(postcard::from_bytes::<CodecConfig>(bytes).unwrap(), config);
+
+    // Whole dB values are accepted, gains off the 1.5dB grid or out of range are not
+    let text = text.replacen("gain_db = -1.5", "gain_db = 3", 1);
+    let parsed = toml::from_str::<CodecConfig>(&text).unwrap();
+    assert_eq!(parsed.left_line_in.gain_half_db, 6);
+    assert!(
+        toml::from_str::<CodecConfig>(&text.replacen("gain_db = 3", "gain_db = 1", 1)).is_err()
+    );
+    assert!(
+        toml::from_str::<CodecConfig>(&text.replacen("gain_db = 3", "gain_db = 13.5", 1)).is_err()
+    );
+
+    let mut line_in = config.left_line_in;
+    assert_eq!(line_in.set_gain_db(0.75), Err(GainError::OffGrid));
+    assert_eq!(line_in.set_gain_db(-36.0), Err(GainError::OutOfRange));
+    assert_eq!(line_in.set_gain_db(f32::NAN), Err(GainError::OutOfRange));
+    assert_eq!(line_in, config.left_line_in);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn register_serde() {
+    let register = WM8731::reset();
+    let text = toml::to_string(&register).unwrap();
+    assert_eq!(text, "address = 15\nvalue = 0\n");
+    assert_eq!(toml::from_str::<Register>(&text).unwrap(), register);
+
+    assert!(toml::from_str::<Register>("address = 10\nvalue = 0\n").is_err());
+    assert!(toml::from_str::<Register>("address = 4\nvalue = 512\n").is_err());
+}
+
+#[test]
+fn simulator_tracks_writes() {
+    let mut sim = simulator::Simulator::new();
+    let mut codec = driver::Wm8731::new(&mut sim);
+
+    codec
+        .left_line_in(|w| {
+            w.volume().nearest_dB(0);
+            w.mute().disable();
+            w.both().enable();
+        })
+        .unwrap();
+    codec
+        .power_down(|w| {
+            w.line_input().power_on();
+            w.adc().power_on();
+            w.power_off().power_on();
+        })
+        .unwrap();
+    codec.activate().map_err(|(_, e)| e).unwrap();
+
+    assert_eq!(sim.writes(), 3);
+    assert_eq!(sim.left_line_in().gain_half_db, 0);
+    assert_eq!(sim.right_line_in().gain_half_db, 0);
+    assert!(sim.left_line_in().both);
+    assert!(!sim.right_line_in().both);
+    assert!(sim.line_in_to_adc());
+    assert!(!sim.dac_to_output());
+    assert!(sim.is_active());
+
+    // Muting one channel leaves the other reaching the ADC
+    sim.write(WM8731::right_line_in(|w| w.mute().enable()));
+    assert!(sim.line_in_to_adc());
+    sim.write(WM8731::left_line_in(|w| w.mute().enable()));
+    assert!(!sim.line_in_to_adc());
+
+    let mut codec = driver::Wm8731::new(&mut sim);
+    codec.reset().unwrap();
+    assert_eq!(sim.register(0), Some(0b0_1001_0111));
+    assert_eq!(sim.register(6), Some(0b0_1001_1111));
+    assert!(!sim.is_active());
+}
+
+#[test]
+fn simulator_joins_adjacent_writes() {
+    use embedded_hal::i2c::{I2c, Operation};
+    use simulator::{Error, Simulator};
+
+    let mut sim = Simulator::new();
+    // One control word split over two writes is still one 2-byte transfer
+    I2c::transaction(
+        &mut sim,
+        0x1a,
+        &mut [Operation::Write(&[0x12]), Operation::Write(&[0x01])],
+    )
+    .unwrap();
+    assert_eq!(sim.writes(), 1);
+    assert!(sim.is_active());
+
+    assert_eq!(
+        I2c::transaction(
+            &mut sim,
+            0x1a,
+            &mut [Operation::Write(&[0x12, 0x00]), Operation::Write(&[0x00])],
+        ),
+        Err(Error::WordLength(3))
+    );
+    // A read ends the transfer, which is received before the read is refused
+    assert_eq!(
+        I2c::transaction(
+            &mut sim,
+            0x1a,
+            &mut [
+                Operation::Write(&[0x12]),
+                Operation::Write(&[0x00]),
+                Operation::Read(&mut [0]),
+            ],
+        ),
+        Err(Error::Read)
+    );
+    assert_eq!(sim.writes(), 2);
+    assert!(!sim.is_active());
+}
+
+#[test]
+fn simulator_bus_errors() {
+    use embedded_hal::i2c::{I2c, NoAcknowledgeSource};
+    use embedded_hal::spi::SpiDevice;
+    use simulator::{Error, Simulator};
+
+    let mut sim = Simulator::new();
+    assert_eq!(
+        I2c::write(&mut sim, 0x1b, &[0x12, 0x01]),
+        Err(Error::Nack(NoAcknowledgeSource::Address))
+    );
+    assert_eq!(
+        I2c::write(&mut sim, 0x1a, &[0x12]),
+        Err(Error::WordLength(1))
+    );
+    assert_eq!(
+        I2c::write(&mut sim, 0x1a, &[0x14, 0x00]),
+        Err(Error::Register(RegisterError::InvalidAddress(10)))
+    );
+    assert_eq!(I2c::read(&mut sim, 0x1a, &mut [0]), Err(Error::Read));
+
+    sim.nack_next();
+    let codec = driver::Wm8731::new(&mut sim);
+    let (codec, e) = codec.activate().err().unwrap();
+    assert_eq!(e, driver::Error::Nack(NoAcknowledgeSource::Data));
+    // The driver is handed back still inactive, so the transition can be retried
+    let codec = codec.activate().map_err(|(_, e)| e).unwrap();
+    codec.release();
+    assert_eq!(sim.writes(), 1);
+    assert!(sim.is_active());
+    sim.write(WM8731::active().inactive());
+
+    SpiDevice::write(&mut sim, &[0x12, 0x01]).unwrap();
+    assert!(sim.is_active());
+}
+
+#[test]
+fn stereo_writes() {
+    let same = WM8731::stereo_headphone_out(
+        |c| c.volume().dB(-6).unwrap(),
+        |c| c.volume().dB(-6).unwrap(),
+    );
+    assert_eq!(
+        &*same,
+        &[Register {
+            address: 2,
+            value: 0b1_0111_0011,
+        }]
+    );
+
+    let different = WM8731::stereo_headphone_out(
+        |c| {
+            c.volume().dB(-6).unwrap();
+            c.both().enable();
+        },
+        |c| c.volume().dB(0).unwrap(),
+    );
+    assert_eq!(
+        &*different,
+        &[
+            Register {
+                address: 2,
+                value: 0b0_0111_0011,
+            },
+            Register {
+                address: 3,
+                value: 0b0_0111_1001,
+            },
+        ]
+    );
+}
+
+#[test]
+fn stereo_shadow_and_simulator_agree() {
+    let mut shadow = shadow::ShadowRegisters::new();
+    let mut sim = simulator::Simulator::new();
+    let mut codec = driver::Wm8731::new(&mut sim);
+
+    for register in shadow
+        .stereo_line_in(|c| c.mute().disable(), |c| c.mute().disable())
+        .iter()
+    {
+        codec.write(*register).unwrap();
+    }
+    for register in shadow
+        .stereo_line_in(|c| c.volume().nearest_dB(6), |c| c.volume().nearest_dB(-6))
+        .iter()
+    {
+        codec.write(*register).unwrap();
+    }
+    codec
+        .stereo_headphone_out(|c| c.volume().mute(), |c| c.volume().mute())
+        .unwrap();
+    shadow.stereo_headphone_out(|c| c.volume().mute(), |c| c.volume().mute());
+
+    assert_eq!(sim.writes(), 4);
+    assert_eq!(sim.registers(), shadow.registers());
+    assert_eq!(sim.left_line_in().gain_half_db, 12);
+    assert_eq!(sim.right_line_in().gain_half_db, -12);
+    assert!(!sim.left_line_in().mute);
+    assert!(!sim.right_line_in().mute);
+    assert_eq!(sim.right_headphone_out().volume_db, None);
+
+    // After a stereo write collapses to one write with the both bit set, a single channel
+    // write only changes that channel
+    let registers =
+        shadow.stereo_line_in(|c| c.volume().nearest_dB(3), |c| c.volume().nearest_dB(3));
+    assert_eq!(registers.len(), 1);
+    for register in registers.iter() {
+        sim.write(*register);
+    }
+    let register = shadow.left_line_in(|c| c.mute().enable());
+    assert_eq!(register.value & stereo::BOTH, 0);
+    sim.write(register);
+
+    assert_eq!(sim.registers(), shadow.registers());
+    assert!(sim.left_line_in().mute);
+    assert!(!sim.right_line_in().mute);
+}
+
+#[test]
+fn validate_conflicts() {
+    use config::{CodecConfig, Deemphasis};
+    use rate_solver::SampleRate;
+    use validate::{validate, Warning};
+
+    let mut config = CodecConfig::new();
+    config.power.chip = true;
+    config.power.dac = true;
+    config.power.output = true;
+    config.analog_audio_path.dac_select = true;
+    config.analog_audio_path.bypass = false;
+    config.digital_audio_path.deemphasis = Deemphasis::Khz48;
+    assert_eq!(validate(&config.registers().unwrap()).count(), 0);
+
+    config.power.dac = false;
+    config.digital_audio_path.deemphasis = Deemphasis::Khz441;
+    config.format.master = true;
+    config.power.oscillator = false;
+    let warnings: std::vec::Vec<_> = validate(&config.registers().unwrap()).collect();
+    assert_eq!(
+        warnings,
+        [
+            Warning::DacSelectedButPoweredDown,
+            Warning::DeemphasisMismatch {
+                deemphasis: Deemphasis::Khz441,
+                dac: SampleRate::Hz48000,
+            },
+            Warning::MasterWithoutOscillator,
+        ]
+    );
+    assert_eq!(
+        std::format!("{}", warnings[1]),
+        "de-emphasis for 44.1kHz but DAC sampling at 48kHz"
+    );
+
+    let registers = [
+        WM8731::analog_audio_path(|c| c.input_select().mic()),
+        WM8731::power_down(|c| {
+            c.adc().power_on();
+            c.power_off().power_on();
+        }),
+    ];
+    assert!(validate(&registers).any(|w| w == Warning::MicSelectedButPoweredDown));
+}
+
+#[test]
+fn driver_tracks_active_state() {
+    let mut sim = simulator::Simulator::new();
+    let mut codec = driver::Wm8731::new(&mut sim);
+
+    codec
+        .sampling(|w| w.sample_rate().adc_48().dac_48())
+        .unwrap();
+    let mut codec = codec.activate().map_err(|(_, e)| e).unwrap();
+    codec.left_headphone_out(|w| w.volume().mute()).unwrap();
+    let mut codec = codec.deactivate().map_err(|(_, e)| e).unwrap();
+    codec
+        .digital_audio_interface_format(|w| w.format().i2s())
+        .unwrap();
+    codec
+        .activate()
+        .map_err(|(_, e)| e)
+        .unwrap()
+        .reset()
+        .map_err(|(_, e)| e)
+        .unwrap()
+        .release();
+
+    assert_eq!(sim.writes(), 7);
+    assert!(!sim.is_active());
+}
+
+#[test]
+fn driver_rejects_writes_that_bypass_the_state() {
+    let mut sim = simulator::Simulator::new();
+    let mut delay = FakeDelay { ns: 0 };
+    let mut codec = driver::Wm8731::new(&mut sim);
+
+    // Activating is only possible by moving to the active driver
+    assert_eq!(
+        codec.write(WM8731::active().active()),
+        Err(driver::Error::State(9))
+    );
+    let power = power_down::PowerDown::new();
+    assert_eq!(
+        codec.run(sequence::power_up(power, &[], 100), &mut delay),
+        Err(driver::Error::State(9))
+    );
+    // The whole sequence is checked first, so nothing before the activation is written
+    let init = config::CodecConfig::new().init_sequence(100).unwrap();
+    assert_eq!(codec.run(init, &mut delay), Err(driver::Error::State(9)));
+    let (codec, e) = codec
+        .run_power_up(power, &[WM8731::reset()], 100, &mut delay)
+        .err()
+        .unwrap();
+    assert_eq!(e, driver::Error::State(15));
+    // Powering up the outputs with the configuration would defeat the pop-free order
+    let outputs_on = WM8731::power_down(|w| w.output().power_on());
+    let (codec, e) = codec
+        .run_power_up(power, &[outputs_on], 100, &mut delay)
+        .err()
+        .unwrap();
+    assert_eq!(e, driver::Error::State(6));
+    codec.release();
+    assert_eq!(sim.writes(), 0);
+    let codec = driver::Wm8731::new(&mut sim);
+
+    let mut codec = codec
+        .run_power_up(power, &[], 100, &mut delay)
+        .map_err(|(_, e)| e)
+        .unwrap();
+    let sampling = WM8731::sampling(|w| w.sample_rate().adc_48().dac_48());
+    let format = WM8731::digital_audio_interface_format(|w| w.format().i2s());
+    for register in [
+        sampling,
+        format,
+        WM8731::reset(),
+        WM8731::active().inactive(),
+    ] {
+        assert_eq!(
+            codec.write(register),
+            Err(driver::Error::State(register.address))
+        );
+    }
+    let path = digital_audio_path::DigitalAudioPath::new();
+    assert_eq!(
+        codec.run(sequence::power_down(power, path), &mut delay),
+        Err(driver::Error::State(9))
+    );
+    codec.left_headphone_out(|w| w.volume().mute()).unwrap();
+    codec.release();
+
+    assert!(sim.is_active());
+}
+
+#[test]
+fn driver_runs_config_power_up() {
+    let mut config = config::CodecConfig::new();
+    config.left_line_in.mute = false;
+    config.analog_audio_path.dac_select = true;
+    config.power.dac = true;
+    config.power.output = true;
+    config.power.chip = true;
+    config.active = true;
+    let mut sim = simulator::Simulator::new();
+    let mut delay = FakeDelay { ns: 0 };
+
+    let mut codec = driver::Wm8731::new(&mut sim);
+    codec.reset().unwrap();
+    let codec = codec
+        .run_power_up(
+            config.power.power_down(),
+            &config.power_up_configuration().unwrap(),
+            100,
+            &mut delay,
+        )
+        .map_err(|(_, e)| e)
+        .unwrap();
+    codec.release();
+
+    assert_eq!(delay.ns, 100_000_000);
+    for register in config.registers().unwrap().iter() {
+        assert_eq!(sim.register(register.address), Some(register.value));
+    }
+    assert!(sim.dac_to_output());
+}
+
+#[test]
+fn framing_layouts() {
+    use digital_audio_interface_format::DigitalAudioInterfaceFormat;
+    use framing::Framing;
+
+    let mut format = DigitalAudioInterfaceFormat::new();
+    format.format().i2s();
+    format.bit_length().bits_24();
+    let framing = Framing::new(&format);
+    let mut words = [0; 2];
+    framing.pack_i32(&[0x1234_5600, -0x100], &mut words);
+    assert_eq!(words, [0x091a_2b00, 0x7fff_ff80]);
+
+    format.format().right_justified();
+    format.bit_length().bits_20();
+    let framing = Framing::new(&format);
+    framing.pack_i16(&[-1, 0x4000], &mut words);
+    assert_eq!(words, [0x000f_fff0, 0x0004_0000]);
+
+    format.format().dsp();
+    format.bit_length().bits_16();
+    format
+        .left_right_dac_clock_swap()
+        .right_channel_dac_data_left();
+    let framing = Framing::new(&format);
+    framing.pack_i16(&[0x1111, 0x2222], &mut words);
+    assert_eq!(words, [0x2222_1111, 0]);
+}
+
+#[test]
+fn framing_round_trips_with_carry() {
+    use digital_audio_interface_format::DigitalAudioInterfaceFormat;
+    use framing::Framing;
+
+    let samples = [i32::MIN, -1, 0x1234_5679, 0x7fff_ffff, 1, -3];
+    for dsp in [false, true].iter() {
+        let mut format = DigitalAudioInterfaceFormat::new();
+        if *dsp {
+            format.format().dsp();
+            format.left_right_phase().data_on_second_rising_edge();
+        } else {
+            format.format().i2s();
+        }
+        format.bit_length().bits_32();
+        let framing = Framing::new(&format);
+
+        let mut words = [0; 6];
+        assert_eq!(framing.pack_i32(&samples, &mut words), 3);
+        let mut unpacked = [0; 6];
+        assert_eq!(framing.unpack_i32(&words, &mut unpacked), 3);
+        // The last bit of the last frame has no next frame to go in
+        assert_eq!(unpacked[..5], samples[..5]);
+        assert_eq!(unpacked[5], -4);
+    }
+}
+
+#[test]
+fn master_timing() {
+    use digital_audio_interface_format::DigitalAudioInterfaceFormat;
+    use timing::timing;
+
+    let mut format = DigitalAudioInterfaceFormat::new();
+    format.master_slave().master();
+    format.format().dsp();
+    format.bit_length().bits_16();
+
+    // USB mode, 44.1kHz from 12MHz
+    let mut sampling = Sampling::new();
+    sampling.sample_rate().usb().adc_441().dac_441();
+    let t = timing(12_000_000, &sampling, &format).unwrap();
+    assert_eq!(t.bclk_hz(), 12_000_000);
+    assert_eq!(t.dac_bits_per_frame(), 272);
+    assert!((t.dac_lrclk_hz() - 44_117.65).abs() < 0.01);
+    assert_eq!(t.dsp_pulse_bclks, Some(1));
+    assert_eq!(t.dsp_pulse_ns(), Some(83));
+
+    // 96kHz from 24.576MHz with the core clock divided by two
+    let mut sampling = Sampling::new();
+    sampling.sample_rate().adc_96().dac_96();
+    sampling.core_clock_divider_select().divided_by_two();
+    format.format().i2s();
+    format.bit_length().bits_24();
+    let t = timing(24_576_000, &sampling, &format).unwrap();
+    assert_eq!(t.bclk_hz(), 3_072_000);
+    assert_eq!(t.adc_lrclk_hz(), 96_000.0);
+    assert_eq!(t.adc_bits_per_frame(), 32);
+    assert_eq!(t.dsp_pulse_bclks, None);
+    assert!(!t.fits_word_length());
+
+    // 8kHz DAC with 48kHz ADC at 384fs
+    let mut sampling = Sampling::new();
+    sampling.base_oversampling_rate().normal_384();
+    sampling.sample_rate().adc_48().dac_8();
+    let t = timing(18_432_000, &sampling, &format).unwrap();
+    assert_eq!(t.dac_lrclk_hz(), 8_000.0);
+    assert_eq!(t.adc_lrclk_hz(), 48_000.0);
+    assert_eq!(t.adc_bits_per_frame(), 96);
+
+    format.master_slave().slave();
+    assert_eq!(
+        timing(18_432_000, &sampling, &format),
+        Err(TimingError::Slave)
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn adc_filter_stores_offset() {
+    use digital_audio_path::DigitalAudioPath;
+    use filters::AdcFilter;
+
+    let mut path = DigitalAudioPath::new();
+    path.hpor().store();
+    let mut filter = AdcFilter::new(&path);
+    let mut samples = [0.25; 96_000];
+    filter.process(&mut samples);
+    assert!((filter.offset() - 0.25).abs() < 1e-6);
+
+    // Disabled with the offset stored, DC stays removed and signal passes unchanged
+    path.adc_hpf().disable();
+    filter.configure(&path);
+    let mut samples = [0.75, 0.25];
+    filter.process(&mut samples);
+    assert!((samples[0] - 0.5).abs() < 1e-6);
+    assert!(samples[1].abs() < 1e-6);
+
+    // Clearing the offset passes DC through
+    path.hpor().clear();
+    filter.configure(&path);
+    let mut samples = [0.25];
+    filter.process(&mut samples);
+    assert_eq!(filter.offset(), 0.0);
+    assert_eq!(samples, [0.25]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn dac_filter_deemphasis() {
+    use digital_audio_path::DigitalAudioPath;
+    use filters::DacFilter;
+
+    let mut path = DigitalAudioPath::new();
+    path.dac_mute().disable();
+    let mut filter = DacFilter::new(&path);
+    assert_eq!(filter.response_db(10_000.0), 0.0);
+    let mut samples = [0.5, -0.5];
+    filter.process(&mut samples);
+    assert_eq!(samples, [0.5, -0.5]);
+
+    path.deemphasis().frequency_441();
+    filter.configure(&path);
+    assert!(filter.response_db(100.0).abs() < 0.05);
+    // The 50/15µs curve is -0.37dB at 1kHz and -4.5dB at 5kHz, falling towards -10.5dB
+    assert!((filter.response_db(1_000.0) + 0.37).abs() < 0.05);
+    assert!((filter.response_db(5_000.0) + 4.5).abs() < 0.5);
+    assert!(filter.response_db(20_000.0) < -8.0);
+
+    // DC passes unchanged once settled
+    let mut samples = [0.5; 1000];
+    filter.process(&mut samples);
+    assert!((samples[999] - 0.5).abs() < 1e-4);
+
+    path.dac_mute().enable();
+    filter.configure(&path);
+    let mut samples = [0.5; 4];
+    filter.process(&mut samples);
+    assert_eq!(samples, [0.0; 4]);
+}
+
+#[cfg(feature = "linux")]
+#[test]
+fn linux_i2c_writes_to_mock_device_file() {
+    use std::fs::{self, OpenOptions};
+    use std::io::ErrorKind;
+
+    use embedded_hal::i2c::I2c;
+    use linux::LinuxI2c;
+
+    let path = std::env::temp_dir().join(std::format!("wm8731-i2c-{}", std::process::id()));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .unwrap();
+
+    // A plain file is only accepted as an explicit mock
+    let error = LinuxI2c::from_file(file.try_clone().unwrap()).unwrap_err();
+    assert_eq!(error.raw_os_error(), Some(libc::ENOTTY));
+
+    let mut codec = driver::Wm8731::new(LinuxI2c::mock_file(file));
+    codec.reset().unwrap();
+    let codec = codec.activate().map_err(|(_, e)| e).unwrap();
+    let mut i2c = codec.release();
+
+    assert_eq!(fs::read(&path).unwrap(), [0x1e, 0x00, 0x12, 0x01]);
+    // Reads come from the file, which is at its end
+    let mut buf = [0; 2];
+    let result = i2c.read(driver::ADDRESS_CSB_LOW, &mut buf);
+    assert!(matches!(result, Err(linux::Error::Io(ref e)) if e.kind() == ErrorKind::UnexpectedEof));
+
+    assert_eq!(
+        LinuxI2c::open(&path).unwrap_err().raw_os_error(),
+        Some(libc::ENOTTY)
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+/// The output of the CLI's `build` or `init` with the given flags
+#[cfg(feature = "cli")]
+fn cli_output(args: &[&str]) -> std::string::String {
+    use clap::Parser;
+    use cli::{Cli, Command};
+
+    let cli = Cli::try_parse_from(core::iter::once("wm8731").chain(args.iter().copied())).unwrap();
+    let (build, init) = match &cli.command {
+        Command::Build(build) => (build, false),
+        Command::Init(build) => (build, true),
+        Command::Decode { .. } => panic!("not a build command"),
+    };
+    let mut out = std::vec::Vec::new();
+    build.print(&mut out, &build.steps(init).unwrap()).unwrap();
+
+    std::string::String::from_utf8(out).unwrap()
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_parses_values() {
+    use cli::*;
+    use rate_solver::SampleRate;
+
+    assert_eq!(parse_db("3dB"), Ok(3.0));
+    assert_eq!(parse_db("-34.5dB"), Ok(-34.5));
+    assert_eq!(parse_db("1.5"), Ok(1.5));
+    assert!(parse_db("loud").is_err());
+
+    assert_eq!(parse_hz("12.288M"), Ok(12_288_000));
+    assert_eq!(parse_hz("11.2896m"), Ok(11_289_600));
+    assert_eq!(parse_hz("48k"), Ok(48_000));
+    assert_eq!(parse_hz("12000000"), Ok(12_000_000));
+    assert!(parse_hz("fast").is_err());
+
+    assert_eq!(parse_rate("44.1k"), Ok(SampleRate::Hz44100));
+    assert_eq!(parse_rate("8018"), Ok(SampleRate::Hz8018));
+    assert!(parse_rate("47k").is_err());
+
+    let format = Register {
+        address: 7,
+        value: 0x4a,
+    };
+    assert_eq!(parse_register("0x0e4a").unwrap(), format);
+    assert_eq!(parse_register("0e4a").unwrap(), format);
+    assert_eq!(parse_register("7=0x4a").unwrap(), format);
+    // R16 does not exist, and R7 only holds 9 bits
+    assert!(parse_register("0x2000").is_err());
+    assert!(parse_register("7=0x200").is_err());
+    assert!(parse_register("zz").is_err());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_prints_build_formats() {
+    let example = [
+        "build",
+        "--line-in-gain",
+        "3dB",
+        "--format",
+        "i2s",
+        "--bits",
+        "24",
+        "--rate",
+        "48k",
+    ];
+    let output = |format: &str| {
+        let mut args = example.to_vec();
+        args.extend_from_slice(&["--output", format]);
+        cli_output(&args)
+    };
+
+    // I²S, 24 bits and 48kHz are the defaults, so only the gain differs
+    assert_eq!(
+        output("hex"),
+        "0x0099  R0 left line in: LineIn { volume: 3dB, mute: enabled, both: disabled }\n\
+         0x0299  R1 right line in: LineIn { volume: 3dB, mute: enabled, both: disabled }\n"
+    );
+    assert_eq!(
+        output("c"),
+        "static const uint16_t wm8731_registers[] = {\n    0x0099, /* R0 */\n    0x0299, /* R1 */\n};\n"
+    );
+    assert_eq!(
+        output("rust"),
+        "const REGISTERS: [wm8731::Register; 2] = [\n\
+         \x20   wm8731::Register { address: 0, value: 0x099 },\n\
+         \x20   wm8731::Register { address: 1, value: 0x099 },\n\
+         ];\n"
+    );
+    assert_eq!(
+        output("i2cset"),
+        "i2cset -y 1 0x1a 0x00 0x99\ni2cset -y 1 0x1a 0x02 0x99\n"
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_prints_init_delays() {
+    let i2cset = cli_output(&["init", "--output", "i2cset", "--settle-ms", "1500"]);
+    let lines: std::vec::Vec<_> = i2cset.lines().collect();
+    assert_eq!(lines.len(), 13);
+    assert_eq!(lines[0], "i2cset -y 1 0x1a 0x1e 0x00");
+    // Activate, wait for VMID, then power up the outputs
+    assert_eq!(lines[10], "i2cset -y 1 0x1a 0x12 0x00");
+    assert_eq!(lines[11], "sleep 1.500");
+    assert_eq!(lines[12], "i2cset -y 1 0x1a 0x0c 0x9f");
+
+    assert!(cli_output(&["init"]).contains("\ndelay   250ms\n"));
+    // C firmware loops over arrays, so the writes after the delay are in an array of their own
+    let c = cli_output(&["init", "--output", "c"]);
+    assert!(c.starts_with("#define WM8731_SETTLE_MS 250\n"));
+    assert!(c.contains("\n    0x1200, /* R9 */\n};\n"));
+    assert!(c.ends_with(
+        "static const uint16_t wm8731_registers_settled[] = {\n    0x0c9f, /* R6 */\n};\n"
+    ));
+    let rust = cli_output(&["init", "--output", "rust"]);
+    assert!(rust.starts_with("const STEPS: [wm8731::sequence::Step; 13] = [\n"));
+    assert!(rust.contains("\n    wm8731::sequence::Step::Delay { ms: 250 },\n"));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_warns_about_requested_settings_only() {
+    use config::{CodecConfig, Input};
+    use validate::Warning;
+
+    // Bypass with the line input powered down is the reset default
+    let mut config = CodecConfig::new();
+    assert!(cli::warnings(&config).unwrap().is_empty());
+
+    config.analog_audio_path.input = Input::Mic;
+    config.power.adc = true;
+    config.power.chip = true;
+    assert_eq!(
+        cli::warnings(&config).unwrap(),
+        [Warning::MicSelectedButPoweredDown]
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn cli_decode_reports_bad_words_and_continues() {
+    use std::string::ToString;
+
+    let mut out = std::vec::Vec::new();
+    let mut errors = std::vec::Vec::new();
+
+    let result = cli::decode_words(&["0x1e00", "zz", "0x2000", "0x1201"], &mut out, &mut errors);
+
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "2 of 4 words could not be read"
+    );
+    assert_eq!(
+        std::string::String::from_utf8(out).unwrap(),
+        "R15 reset\nR9 active\n"
+    );
+    assert_eq!(
+        std::string::String::from_utf8(errors).unwrap(),
+        "error: `zz`: invalid digit found in string\n\
+         error: `0x2000`: R16 is not a register\n"
+    );
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn errors_display_readable_messages() {
+    use std::string::ToString;
+
+    assert_eq!(
+        ConfigError::Gain(GainError::OffGrid).to_string(),
+        "gain is between two steps"
+    );
+    let rates = rate_solver::solve(
+        12_288_000,
+        rate_solver::SampleRate::Hz44100,
+        rate_solver::SampleRate::Hz48000,
+    );
+    assert!(rates.unwrap_err().to_string().starts_with(
+        "sample rates are not achievable with this MCLK; ADC/DAC can be 48kHz/48kHz, "
+    ));
+    assert_eq!(
+        DecodeError::ReservedSampleRate(0b1111).to_string(),
+        "sample rate code 0b1111 is reserved"
+    );
+    assert_eq!(
+        driver::Error::<simulator::Error>::State(8).to_string(),
+        "R8 cannot be written in this interface state"
+    );
+    assert_eq!(
+        simulator::Error::Nack(embedded_hal::i2c::NoAcknowledgeSource::Data).to_string(),
+        "the codec did not acknowledge the control word"
+    );
+}
@@ -0,0 +1,152 @@
+//! Bit clock and frame timing in master mode
+//!
+//! In master mode the codec derives BCLK and the DACLRC/ADCLRC frame clocks from MCLK. The
+//! other end of the digital audio interface has to be set up for the same timing; [`timing`]
+//! works it out from the sampling control and digital audio interface format settings.
+//!
+//! ```
+//! # use wm8731::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+//! # use wm8731::sampling::Sampling;
+//! # use wm8731::timing::timing;
+//! let mut sampling = Sampling::new();
+//! sampling.sample_rate().adc_48().dac_48();
+//!
+//! let mut format = DigitalAudioInterfaceFormat::new();
+//! format.master_slave().master();
+//! format.bit_length().bits_24();
+//!
+//! let timing = timing(12_288_000, &sampling, &format).unwrap();
+//! assert_eq!(timing.bclk_hz(), 3_072_000);
+//! assert_eq!(timing.dac_lrclk_hz(), 48_000.0);
+//! assert_eq!(timing.dac_bits_per_frame(), 64);
+//! ```
+
+use crate::config::AudioFormat;
+use crate::decode;
+use crate::digital_audio_interface_format::DigitalAudioInterfaceFormat;
+use crate::rate_solver::SampleRate;
+use crate::sampling::Sampling;
+use crate::TimingError;
+
+/// Clocks generated by the codec in master mode
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timing {
+    pub mclk_hz: u32,
+    /// MCLK cycles per BCLK cycle
+    pub bclk_divider: u32,
+    /// MCLK cycles per DACLRC frame
+    pub dac_lrclk_divider: u32,
+    /// MCLK cycles per ADCLRC frame
+    pub adc_lrclk_divider: u32,
+    /// Bits in each sample
+    pub word_bits: u32,
+    /// Width of the DACLRC/ADCLRC frame sync pulse in BCLK cycles, in DSP mode
+    pub dsp_pulse_bclks: Option<u32>,
+}
+
+impl Timing {
+    /// BCLK frequency
+    pub fn bclk_hz(&self) -> u32 {
+        self.mclk_hz / self.bclk_divider
+    }
+
+    /// DACLRC frequency, the DAC sample rate
+    pub fn dac_lrclk_hz(&self) -> f32 {
+        self.mclk_hz as f32 / self.dac_lrclk_divider as f32
+    }
+
+    /// ADCLRC frequency, the ADC sample rate
+    pub fn adc_lrclk_hz(&self) -> f32 {
+        self.mclk_hz as f32 / self.adc_lrclk_divider as f32
+    }
+
+    /// BCLK cycles in each DACLRC frame
+    pub fn dac_bits_per_frame(&self) -> u32 {
+        self.dac_lrclk_divider / self.bclk_divider
+    }
+
+    /// BCLK cycles in each ADCLRC frame
+    pub fn adc_bits_per_frame(&self) -> u32 {
+        self.adc_lrclk_divider / self.bclk_divider
+    }
+
+    /// Width of the frame sync pulse in DSP mode, in nanoseconds
+    pub fn dsp_pulse_ns(&self) -> Option<u32> {
+        self.dsp_pulse_bclks
+            .map(|bclks| (u64::from(bclks) * 1_000_000_000 / u64::from(self.bclk_hz())) as u32)
+    }
+
+    /// Whether a frame has room for both channels at the word length
+    ///
+    /// At high sample rates there are not always enough bit clocks in a frame, in which case
+    /// the word length has to be reduced.
+    pub fn fits_word_length(&self) -> bool {
+        let needed = 2 * self.word_bits;
+
+        self.dac_bits_per_frame() >= needed && self.adc_bits_per_frame() >= needed
+    }
+}
+
+/// Core clock cycles per sample
+fn oversampling(usb: bool, bosr: bool, rate: SampleRate) -> u32 {
+    use SampleRate::*;
+
+    if usb {
+        match rate {
+            Hz8000 => 1500,
+            Hz8018 | Hz8021 => 1496,
+            Hz32000 => 375,
+            Hz44100 => 272,
+            Hz48000 => 250,
+            Hz88200 => 136,
+            Hz96000 => 125,
+        }
+    } else {
+        // In 1/2 fs, relative to the base over-sampling rate
+        let base = if bosr { 384 } else { 256 };
+        let halves = match rate {
+            Hz8000 => 12,
+            Hz8018 | Hz8021 => 11,
+            Hz32000 => 3,
+            Hz44100 | Hz48000 => 2,
+            Hz88200 | Hz96000 => 1,
+        };
+        base * halves / 2
+    }
+}
+
+/// Clocks the codec generates in master mode from an MCLK of `mclk_hz`
+///
+/// Fails if `format` is not set to master mode or `sampling` holds a reserved sample rate code.
+pub fn timing(
+    mclk_hz: u32,
+    sampling: &Sampling,
+    format: &DigitalAudioInterfaceFormat,
+) -> Result<Timing, TimingError> {
+    let format = decode::digital_audio_interface_format(format.data);
+    if !format.master {
+        return Err(TimingError::Slave);
+    }
+    let sampling = decode::sampling(sampling.data)
+        .map_err(|_| TimingError::ReservedSampleRate(((sampling.data >> 2) & 0b1111) as u8))?;
+
+    let core_divider = if sampling.core_clock_divide_by_two {
+        2
+    } else {
+        1
+    };
+    // BCLK runs at MCLK in USB mode, and at MCLK/4 in normal mode
+    let bclk_divider = if sampling.usb { 1 } else { 4 };
+
+    Ok(Timing {
+        mclk_hz,
+        bclk_divider: core_divider * bclk_divider,
+        dac_lrclk_divider: core_divider * oversampling(sampling.usb, sampling.bosr, sampling.dac),
+        adc_lrclk_divider: core_divider * oversampling(sampling.usb, sampling.bosr, sampling.adc),
+        word_bits: format.word_length.bits(),
+        dsp_pulse_bclks: match format.format {
+            AudioFormat::Dsp => Some(1),
+            _ => None,
+        },
+    })
+}
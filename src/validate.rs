@@ -0,0 +1,137 @@
+//! Check a set of registers for settings that contradict each other
+//!
+//! Each register can be valid on its own while the combination makes no sense, such as
+//! selecting the DAC while it is powered down. [`validate`] looks at every register together and
+//! reports each conflict as a [`Warning`].
+//!
+//! ```
+//! # use wm8731::validate::{validate, Warning};
+//! # use wm8731::WM8731;
+//! let registers = [
+//!     WM8731::analog_audio_path(|c| c.dac_select().select()),
+//!     WM8731::power_down(|c| c.power_off().power_on()),
+//! ];
+//!
+//! let mut warnings = validate(&registers);
+//! assert_eq!(warnings.next(), Some(Warning::DacSelectedButPoweredDown));
+//! ```
+
+use core::fmt;
+
+use crate::config::{Deemphasis, Input};
+use crate::decode;
+use crate::rate_solver::SampleRate;
+use crate::shadow::ShadowRegisters;
+use crate::Register;
+
+/// Settings in different registers that contradict each other
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Warning {
+    /// The DAC feeds the outputs but is powered down
+    DacSelectedButPoweredDown,
+    /// The microphone is the ADC input but is powered down
+    MicSelectedButPoweredDown,
+    /// The line input is the ADC input while the ADC is powered, but the line input is powered
+    /// down
+    LineInputSelectedButPoweredDown,
+    /// The line input bypasses to the outputs but is powered down
+    BypassButLineInputPoweredDown,
+    /// The microphone side tone feeds the outputs but the microphone is powered down
+    SidetoneButMicPoweredDown,
+    /// The de-emphasis filter is set for a different sample rate than the DAC's
+    DeemphasisMismatch {
+        deemphasis: Deemphasis,
+        dac: SampleRate,
+    },
+    /// The codec generates BCLK and LRCLK in master mode, but the oscillator is powered down
+    MasterWithoutOscillator,
+    /// The digital audio interface is active while the whole chip is powered down
+    ActiveWhilePoweredOff,
+    /// The sampling control register holds a sample rate code the datasheet reserves
+    ReservedSampleRate,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::DacSelectedButPoweredDown => f.write_str("DAC selected but powered down"),
+            Warning::MicSelectedButPoweredDown => {
+                f.write_str("mic selected as ADC input but powered down")
+            }
+            Warning::LineInputSelectedButPoweredDown => {
+                f.write_str("line input selected as ADC input but powered down")
+            }
+            Warning::BypassButLineInputPoweredDown => {
+                f.write_str("bypass enabled but line input powered down")
+            }
+            Warning::SidetoneButMicPoweredDown => {
+                f.write_str("side tone enabled but mic powered down")
+            }
+            Warning::DeemphasisMismatch { deemphasis, dac } => write!(
+                f,
+                "de-emphasis for {} but DAC sampling at {}",
+                deemphasis, dac
+            ),
+            Warning::MasterWithoutOscillator => {
+                f.write_str("master mode but oscillator powered down")
+            }
+            Warning::ActiveWhilePoweredOff => f.write_str("interface active but chip powered off"),
+            Warning::ReservedSampleRate => f.write_str("reserved sample rate code"),
+        }
+    }
+}
+
+/// Whether the de-emphasis filter matches the DAC sample rate
+fn deemphasis_matches(deemphasis: Deemphasis, dac: SampleRate) -> bool {
+    match deemphasis {
+        Deemphasis::Disabled => true,
+        Deemphasis::Khz32 => dac == SampleRate::Hz32000,
+        Deemphasis::Khz441 => dac == SampleRate::Hz44100,
+        Deemphasis::Khz48 => dac == SampleRate::Hz48000,
+    }
+}
+
+/// Every conflict between the given registers
+///
+/// The registers are applied in order on top of the reset defaults, so `registers` can be a
+/// whole set such as [`ShadowRegisters::registers`] or just the writes made since reset.
+/// Sample rates are the nominal ones for the sample rate code, as in [`decode`].
+pub fn validate(registers: &[Register]) -> impl Iterator<Item = Warning> {
+    let mut shadow = ShadowRegisters::new();
+    for register in registers {
+        shadow.record(*register);
+    }
+    let value = |address| shadow.get(address).unwrap_or(0);
+
+    let path = decode::analog_audio_path(value(4));
+    let digital = decode::digital_audio_path(value(5));
+    let power = decode::power_down(value(6));
+    let format = decode::digital_audio_interface_format(value(7));
+    let sampling = decode::sampling(value(8));
+    let adc_in_use = power.chip && power.adc;
+
+    let warnings = [
+        (path.dac_select && !power.dac).then_some(Warning::DacSelectedButPoweredDown),
+        (adc_in_use && path.input == Input::Mic && !power.mic)
+            .then_some(Warning::MicSelectedButPoweredDown),
+        (adc_in_use && path.input == Input::LineInput && !power.line_input)
+            .then_some(Warning::LineInputSelectedButPoweredDown),
+        (path.bypass && !power.line_input).then_some(Warning::BypassButLineInputPoweredDown),
+        (path.sidetone && !power.mic).then_some(Warning::SidetoneButMicPoweredDown),
+        match sampling {
+            Ok(s) if !deemphasis_matches(digital.deemphasis, s.dac) => {
+                Some(Warning::DeemphasisMismatch {
+                    deemphasis: digital.deemphasis,
+                    dac: s.dac,
+                })
+            }
+            Ok(_) => None,
+            Err(_) => Some(Warning::ReservedSampleRate),
+        },
+        (format.master && !power.oscillator).then_some(Warning::MasterWithoutOscillator),
+        (shadow.is_active() && !power.chip).then_some(Warning::ActiveWhilePoweredOff),
+    ];
+
+    IntoIterator::into_iter(warnings).flatten()
+}